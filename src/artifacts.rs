@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+use crate::rules::wildcard_match;
+use crate::{compute_dir_stats, EntryFilter};
+
+/// Workspace roots to search for build output of idle projects (`RunOverrides::sweep`).
+#[derive(Debug, Clone)]
+pub struct ArtifactSweep {
+    pub roots: Vec<PathBuf>,
+    /// Projects with anything modified more recently than this are left alone
+    pub min_idle: Duration,
+}
+
+/// A build output directory found by a sweep.
+#[derive(Debug, Clone, Serialize)]
+pub struct Artifact {
+    pub path: PathBuf,
+    /// Folder holding the marker file (e.g. the one with `Cargo.toml`)
+    pub project: PathBuf,
+    /// Kind of output, also used as the run category (e.g. `cargo_target`)
+    pub category: &'static str,
+    pub bytes: u64,
+    pub bytes_allocated: u64,
+}
+
+/// One kind of build output: a directory name and the marker file next to it that identifies it.
+struct Kind {
+    category: &'static str,
+    dirs: &'static [&'static str],
+    /// Any of these (wildcards allowed) must exist next to the directory
+    markers: &'static [&'static str],
+    /// If not empty, any of these must exist inside the directory as well
+    inside: &'static [&'static str],
+}
+
+const KINDS: &[Kind] = &[
+    Kind { category: "cargo_target", dirs: &["target"], markers: &["Cargo.toml"], inside: &["CACHEDIR.TAG", ".rustc_info.json"] },
+    Kind { category: "node_modules", dirs: &["node_modules"], markers: &["package.json"], inside: &[] },
+    Kind { category: "dotnet_build", dirs: &["bin", "obj"], markers: &["*.csproj", "*.fsproj", "*.vbproj"], inside: &[] },
+    Kind { category: "python_bytecode", dirs: &["__pycache__"], markers: &["*.py"], inside: &["*.pyc"] },
+    Kind {
+        category: "gradle_project_cache",
+        dirs: &[".gradle"],
+        markers: &["build.gradle", "build.gradle.kts", "settings.gradle", "settings.gradle.kts"],
+        inside: &[],
+    },
+];
+
+/// Never searched, and not counted when deciding whether a project was touched.
+const SKIPPED_DIRS: &[&str] = &[".git", ".hg", ".svn"];
+
+fn any_entry(names: &[String], patterns: &[&str]) -> bool {
+    patterns.iter().any(|p| names.iter().any(|n| wildcard_match(p, n)))
+}
+
+fn entry_names(dir: &Path) -> Vec<String> {
+    match fs::read_dir(dir) {
+        Ok(rd) => rd.flatten().map(|e| e.file_name().to_string_lossy().to_string()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Kind of `name` inside a folder with the entries `siblings`, if it is a build output directory.
+fn kind_of(dir: &Path, name: &str, siblings: &[String]) -> Option<&'static Kind> {
+    let kind = KINDS.iter().find(|k| k.dirs.contains(&name) && any_entry(siblings, k.markers))?;
+    if !kind.inside.is_empty() && !any_entry(&entry_names(&dir.join(name)), kind.inside) { return None; }
+    Some(kind)
+}
+
+/// Whether `p` is still a build output directory identified by its marker file.
+pub(crate) fn is_artifact_dir(p: &Path) -> bool {
+    let (Some(parent), Some(name)) = (p.parent(), p.file_name()) else { return false };
+    let is_dir = fs::symlink_metadata(p).map(|m| m.is_dir()).unwrap_or(false);
+    is_dir && kind_of(parent, &name.to_string_lossy(), &entry_names(parent)).is_some()
+}
+
+/// Whether anything below `dir` (build output and VCS folders aside) was modified after `cutoff`.
+fn touched_since(dir: &Path, cutoff: SystemTime) -> bool {
+    let Ok(rd) = fs::read_dir(dir) else { return false };
+    let entries: Vec<fs::DirEntry> = rd.flatten().collect();
+    let names: Vec<String> = entries.iter().map(|e| e.file_name().to_string_lossy().to_string()).collect();
+    for (e, name) in entries.iter().zip(&names) {
+        let Ok(md) = fs::symlink_metadata(e.path()) else { continue };
+        if md.is_dir() {
+            if SKIPPED_DIRS.contains(&name.as_str()) || kind_of(dir, name, &names).is_some() { continue; }
+            if touched_since(&e.path(), cutoff) { return true; }
+        } else if md.modified().is_ok_and(|t| t > cutoff) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Build output directories below the sweep roots whose projects have been idle long enough.
+pub(crate) fn discover(sweep: &ArtifactSweep) -> Vec<(PathBuf, PathBuf, &'static str)> {
+    let cutoff = SystemTime::now().checked_sub(sweep.min_idle).unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut idle: HashMap<PathBuf, bool> = HashMap::new();
+    let mut out = Vec::new();
+    let mut stack: Vec<PathBuf> = sweep.roots.iter().filter(|r| r.is_dir()).cloned().collect();
+    while let Some(dir) = stack.pop() {
+        let Ok(rd) = fs::read_dir(&dir) else { continue };
+        let entries: Vec<fs::DirEntry> = rd.flatten().collect();
+        let names: Vec<String> = entries.iter().map(|e| e.file_name().to_string_lossy().to_string()).collect();
+        for (e, name) in entries.iter().zip(&names) {
+            // Never follow links out of the workspace
+            if !e.file_type().is_ok_and(|t| t.is_dir()) || SKIPPED_DIRS.contains(&name.as_str()) { continue; }
+            let Some(kind) = kind_of(&dir, name, &names) else {
+                stack.push(e.path());
+                continue;
+            };
+            let project_idle = *idle.entry(dir.clone()).or_insert_with(|| !touched_since(&dir, cutoff));
+            if project_idle { out.push((e.path(), dir.clone(), kind.category)); }
+        }
+    }
+    out.sort();
+    out
+}
+
+/// Build output directories of idle projects below the sweep roots, with their sizes.
+pub fn find_artifacts(sweep: &ArtifactSweep, threads: usize) -> Vec<Artifact> {
+    discover(sweep).into_iter().map(|(path, project, category)| {
+        let count = compute_dir_stats(&path, &EntryFilter::default(), threads);
+        Artifact { path, project, category, bytes: count.bytes, bytes_allocated: count.allocated }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_only_marked_output_of_idle_projects() {
+        let root = std::env::temp_dir().join(format!("zentify-test-artifacts-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let write = |p: &str, data: &[u8]| {
+            let p = root.join(p);
+            fs::create_dir_all(p.parent().unwrap()).unwrap();
+            fs::write(p, data).unwrap();
+        };
+        write("rust/Cargo.toml", b"[package]");
+        write("rust/target/CACHEDIR.TAG", b"Signature");
+        write("rust/target/debug/app", b"1234");
+        write("web/package.json", b"{}");
+        write("web/node_modules/left-pad/package.json", b"{}");
+        write("web/node_modules/left-pad/node_modules/x/package.json", b"{}");
+        write("net/App.csproj", b"<Project/>");
+        write("net/obj/project.assets.json", b"{}");
+        // Same names without a marker, or without the cargo tag inside, are not build output
+        write("docs/target/keep.txt", b"1");
+        write("docs/bin/tool.sh", b"1");
+        write("half/Cargo.toml", b"[package]");
+        write("half/target/notes.txt", b"1");
+
+        let sweep = ArtifactSweep { roots: vec![root.clone()], min_idle: Duration::ZERO };
+        let found: Vec<(PathBuf, &str)> = discover(&sweep).into_iter().map(|(p, _, c)| (p, c)).collect();
+        assert_eq!(found, vec![
+            (root.join("net/obj"), "dotnet_build"),
+            (root.join("rust/target"), "cargo_target"),
+            (root.join("web/node_modules"), "node_modules"),
+        ]);
+        assert!(is_artifact_dir(&root.join("rust/target")) && !is_artifact_dir(&root.join("docs/target")));
+        let sized = find_artifacts(&sweep, 2);
+        assert_eq!(sized.iter().find(|a| a.category == "cargo_target").unwrap().bytes, 13);
+
+        // Everything was just written, so no project has been idle for a year
+        let recent = ArtifactSweep { roots: vec![root.clone()], min_idle: Duration::from_secs(365 * 86400) };
+        assert!(discover(&recent).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
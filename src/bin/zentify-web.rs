@@ -14,12 +14,10 @@ use serde::{Deserialize, Serialize};
 use tokio::{net::TcpListener, time::timeout, sync::Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use rand::{rngs::OsRng, RngCore};
-use zentify_cleaner::{Config, load_config, run_clean, RunOverrides, format_bytes, env_truthy, is_elevated, preview_targets, TargetsPreview};
+use zentify_cleaner::{Config, load_config, run_clean, RunOverrides, format_bytes, env_truthy, is_elevated, preview_targets, TargetsPreview, QuarantineInfo, CleanEvent, CleanObserver, EntryKind, Phase, CancellationToken, Summary, CleanFailure, VolumeDelta, BudgetReport};
 #[cfg(windows)]
 use std::os::windows::ffi::OsStrExt;
-#[cfg(windows)]
 // No Security import needed here; use is_elevated() from library
-#[cfg(windows)]
 // Call ShellExecuteW via fully-qualified path at call site
 
 #[derive(Clone)]
@@ -43,6 +41,10 @@ struct RunRequest {
     allow_system_clean: bool,
     prefetch: bool,
     max_parallelism: Option<u32>,
+    #[serde(default)]
+    quarantine: bool,
+    #[serde(default)]
+    target_free_bytes: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -55,10 +57,17 @@ struct RunResponse {
     dirs_deleted: u64,
     links_removed: u64,
     bytes_freed: u64,
+    bytes_allocated: u64,
+    bytes_not_freed: u64,
     elapsed: f64,
     dry_run: bool,
     exact_stats: bool,
     cleaned_dirs: Vec<String>,
+    quarantine: Option<QuarantineInfo>,
+    volumes: Vec<VolumeDelta>,
+    budget: Option<BudgetReport>,
+    cancelled: bool,
+    failures: Vec<CleanFailure>,
 }
 
 #[derive(Debug, Serialize)]
@@ -102,11 +111,91 @@ struct RunRecord {
 #[derive(Debug, Serialize, Clone)]
 struct JobStatusData {
     id: String,
-    status: String, // running|completed|failed|timeout
+    status: String, // running|completed|cancelled|failed|timeout
     started_at: u64,
     finished_at: Option<u64>,
     result: Option<RunResponse>,
     error: Option<String>,
+    progress: Option<RunProgress>,
+    #[serde(skip)]
+    tracker: Option<Arc<ProgressTracker>>,
+    #[serde(skip)]
+    cancel: Option<CancellationToken>,
+}
+
+/// Live counters of a running job, fed by the cleaner's progress events.
+#[derive(Debug, Serialize, Clone, Default)]
+struct RunProgress {
+    phase: Option<Phase>,
+    current_target: Option<String>,
+    targets_done: u64,
+    files: u64,
+    dirs: u64,
+    links: u64,
+    bytes: u64,
+    failures: u64,
+}
+
+#[derive(Debug, Default)]
+struct ProgressTracker {
+    dry_run: bool,
+    progress: std::sync::Mutex<RunProgress>,
+    /// Per-entry lines for verbose runs (capped)
+    log: Option<std::sync::Mutex<Vec<String>>>,
+}
+
+impl ProgressTracker {
+    fn new(verbose: bool, dry_run: bool) -> Self {
+        Self { dry_run, progress: Default::default(), log: verbose.then(Default::default) }
+    }
+
+    fn snapshot(&self) -> RunProgress {
+        self.progress.lock().map(|p| p.clone()).unwrap_or_default()
+    }
+
+    fn take_log(&self) -> String {
+        let Some(log) = &self.log else { return String::new() };
+        log.lock().map(|mut l| l.drain(..).map(|s| s + "\n").collect()).unwrap_or_default()
+    }
+
+    fn push_log(&self, line: String) {
+        const MAX_LOG_LINES: usize = 2000;
+        if let Some(log) = &self.log {
+            if let Ok(mut l) = log.lock() {
+                if l.len() < MAX_LOG_LINES { l.push(line); }
+            }
+        }
+    }
+}
+
+impl CleanObserver for ProgressTracker {
+    fn on_event(&self, event: &CleanEvent) {
+        if let Ok(mut p) = self.progress.lock() {
+            match event {
+                CleanEvent::PhaseChanged(phase) => p.phase = Some(*phase),
+                CleanEvent::TargetStarted { path, .. } => p.current_target = Some(path.to_string_lossy().to_string()),
+                CleanEvent::TargetFinished { .. } => p.targets_done += 1,
+                CleanEvent::EntryRemoved { kind, files, dirs, bytes, .. } => {
+                    if *kind == EntryKind::Link { p.links += 1; }
+                    p.files += files;
+                    p.dirs += dirs;
+                    p.bytes += bytes;
+                }
+                CleanEvent::EntryScheduled { bytes, .. } => { p.files += 1; p.bytes += bytes; }
+                CleanEvent::EntryFailed { .. } => p.failures += 1,
+                CleanEvent::TargetSkipped { .. } => {}
+            }
+        }
+        match event {
+            CleanEvent::EntryRemoved { path, files, dirs, bytes, .. } => {
+                let verb = if self.dry_run { "[dry-run] Would remove" } else { "Removed" };
+                self.push_log(format!("{}: {} ({} files, {} dirs, {} bytes)", verb, path.display(), files, dirs, bytes));
+            }
+            CleanEvent::EntryScheduled { path, bytes } => self.push_log(format!("Scheduled for deletion on reboot: {} ({} bytes)", path.display(), bytes)),
+            CleanEvent::EntryFailed { path, error } => self.push_log(format!("Could not remove {}: {}", path.display(), error)),
+            _ => {}
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -216,6 +305,7 @@ async fn main() {
         .route("/api/run", post(run_cleaner))
         .route("/api/run-async", post(run_cleaner_async))
         .route("/api/job/:id", get(job_status).delete(delete_job))
+        .route("/api/job/:id/cancel", post(cancel_job))
         .layer(DefaultBodyLimit::max(32 * 1024))
         .with_state(state);
 
@@ -299,11 +389,14 @@ async fn build_cfg_and_overrides(state: &AppState, req: &RunRequest) -> (Config,
     if req.verbose { cfg.verbose = true; cfg.quiet = false; }
     if req.quiet { cfg.quiet = true; cfg.verbose = false; }
     if req.exact_stats { cfg.exact_stats = true; }
+    if req.quarantine { cfg.quarantine = true; }
 
     let overrides = RunOverrides {
         allow_system: req.allow_system_clean,
         prefetch: Some(req.prefetch),
         max_parallelism: req.max_parallelism.map(|n| n as usize),
+        target_free_bytes: req.target_free_bytes,
+        ..Default::default()
     };
     (cfg, overrides)
 }
@@ -315,56 +408,114 @@ async fn push_history(state: &AppState, id: Option<String>, started_at: u64, fin
     if h.len() > MAX_HIST { h.truncate(MAX_HIST); }
 }
 
-async fn run_cleaner(State(state): State<AppState>, headers: HeaderMap, Json(req): Json<RunRequest>) -> Result<Json<RunResponse>, (StatusCode, String)> {
-    // CSRF validation: require the exact per-process token
-    csrf_check(&headers, &state)?;
-
-    // Build config (respect in-memory override) and overrides
-    let (cfg, overrides) = build_cfg_and_overrides(&state, &req).await;
-
-    // Run heavy sync cleaning logic on blocking thread
-    let handle = tokio::task::spawn_blocking(move || { run_clean(&cfg, &overrides) });
-
-    let timeout_secs: u64 = std::env::var("ZENTIFY_WEB_RUN_TIMEOUT_SECS")
-        .ok()
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(600);
-
-    let started_at = now_unix();
-    let summary = match timeout(Duration::from_secs(timeout_secs), handle).await {
-        Ok(join_res) => join_res.map_err(internal_error)?,
-        Err(_) => {
-            return Err((StatusCode::REQUEST_TIMEOUT, format!("cleaner timed out after {}s", timeout_secs)));
-        }
-    };
+fn run_timeout() -> Duration {
+    let secs = std::env::var("ZENTIFY_WEB_RUN_TIMEOUT_SECS").ok().and_then(|s| s.parse::<u64>().ok()).unwrap_or(600);
+    Duration::from_secs(secs)
+}
 
-    let mut stdout = String::new();
+/// Builds the API response (and the CLI-style text output) for a finished run.
+fn response_from_summary(summary: Summary, log: String, timed_out: Option<Duration>) -> RunResponse {
+    let mut stdout = log;
+    let mut stderr = String::new();
     if summary.dry_run {
         stdout.push_str(&format!(
-            "Dry-run summary: would remove {} files, {} dirs, {} links; free approx {} ({} bytes) in {:?}.\n",
+            "Dry-run summary: would remove {} files, {} dirs, {} links; free approx {} ({} bytes, {} on disk) in {:?}.\n",
             summary.files_deleted,
             summary.dirs_deleted,
             summary.links_removed,
             format_bytes(summary.bytes_freed),
             summary.bytes_freed,
+            format_bytes(summary.bytes_allocated),
             summary.elapsed
         ));
     } else {
         stdout.push_str(&format!(
-            "Summary: removed {} files, {} dirs, {} links; freed {} ({} bytes) in {:?}.\n",
+            "Summary: removed {} files, {} dirs, {} links; freed {} ({} bytes, {} on disk) in {:?}.\n",
             summary.files_deleted,
             summary.dirs_deleted,
             summary.links_removed,
             format_bytes(summary.bytes_freed),
             summary.bytes_freed,
+            format_bytes(summary.bytes_allocated),
             summary.elapsed
         ));
         if !summary.exact_stats {
             stdout.push_str("Note: Byte counts for directories are approximate (fast mode). Use --exact-stats for precise totals.\n");
         }
+        if let Some(q) = &summary.quarantine {
+            stdout.push_str(&format!("Quarantined to {} (run id {}).\n", q.path, q.run_id));
+        }
+        if summary.bytes_not_freed > 0 {
+            stdout.push_str(&format!("Not actually freed: {} of hard-linked files still reachable through other links.\n", format_bytes(summary.bytes_not_freed)));
+        }
+        for v in &summary.volumes {
+            stdout.push_str(&format!("Volume {}\n", v));
+        }
+    }
+    if let Some(b) = &summary.budget {
+        stdout.push_str(&format!("{}\n", b));
+    }
+    if let Some(t) = timed_out {
+        stderr.push_str(&format!("cleaner timed out after {}s and was cancelled\n", t.as_secs()));
+    } else if summary.cancelled {
+        stderr.push_str("run cancelled\n");
+    }
+    for f in &summary.failures {
+        stderr.push_str(&format!("{}\n", f));
+    }
+    let exit_code = summary.exit_code();
+    RunResponse {
+        ok: exit_code == 0,
+        exit_code,
+        stdout,
+        stderr,
+        files_deleted: summary.files_deleted,
+        dirs_deleted: summary.dirs_deleted,
+        links_removed: summary.links_removed,
+        bytes_freed: summary.bytes_freed,
+        bytes_allocated: summary.bytes_allocated,
+        bytes_not_freed: summary.bytes_not_freed,
+        elapsed: summary.elapsed.as_secs_f64(),
+        dry_run: summary.dry_run,
+        exact_stats: summary.exact_stats,
+        cleaned_dirs: summary.cleaned_dirs,
+        quarantine: summary.quarantine,
+        volumes: summary.volumes,
+        budget: summary.budget,
+        cancelled: summary.cancelled,
+        failures: summary.failures,
+    }
+}
+
+/// Runs the cleaner on a blocking thread. On timeout the run is cancelled and its partial summary
+/// awaited, so no deletion continues in the background. Returns the summary and whether it timed out.
+async fn run_with_timeout(cfg: Config, overrides: RunOverrides, cancel: CancellationToken) -> Result<(Summary, bool), tokio::task::JoinError> {
+    let mut handle = tokio::task::spawn_blocking(move || { run_clean(&cfg, &overrides) });
+    match timeout(run_timeout(), &mut handle).await {
+        Ok(join_res) => join_res.map(|summary| (summary, false)),
+        Err(_) => {
+            cancel.cancel();
+            handle.await.map(|summary| (summary, true))
+        }
     }
+}
+
+async fn run_cleaner(State(state): State<AppState>, headers: HeaderMap, Json(req): Json<RunRequest>) -> Result<Json<RunResponse>, (StatusCode, String)> {
+    // CSRF validation: require the exact per-process token
+    csrf_check(&headers, &state)?;
+
+    // Build config (respect in-memory override) and overrides
+    let (cfg, mut overrides) = build_cfg_and_overrides(&state, &req).await;
+    let tracker = Arc::new(ProgressTracker::new(cfg.verbose && !cfg.quiet, cfg.dry_run));
+    overrides.observer = Some(tracker.clone());
+    let cancel = CancellationToken::new();
+    overrides.cancel = Some(cancel.clone());
 
-    let resp = RunResponse { ok: true, exit_code: 0, stdout, stderr: String::new(), files_deleted: summary.files_deleted, dirs_deleted: summary.dirs_deleted, links_removed: summary.links_removed, bytes_freed: summary.bytes_freed, elapsed: summary.elapsed.as_secs_f64(), dry_run: summary.dry_run, exact_stats: summary.exact_stats, cleaned_dirs: summary.cleaned_dirs };
+    // Run heavy sync cleaning logic on blocking thread
+    let started_at = now_unix();
+    let (summary, timed_out) = run_with_timeout(cfg, overrides, cancel).await.map_err(internal_error)?;
+
+    let resp = response_from_summary(summary, tracker.take_log(), timed_out.then(run_timeout));
     let finished_at = now_unix();
     push_history(&state, None, started_at, finished_at, resp.clone()).await;
     Ok(Json(resp))
@@ -418,10 +569,15 @@ const INDEX_HTML: &str = r#"<!doctype html>
         <div class="row"><label><input type="checkbox" id="exact_stats"> Exakte Statistiken (langsamer)</label></div>
         <div class="row"><label><input type="checkbox" id="allow_system_clean"> Systemweite Bereiche erlauben (Risiko!)</label></div>
         <div class="row"><label><input type="checkbox" id="prefetch"> Prefetch bereinigen</label></div>
+        <div class="row"><label><input type="checkbox" id="quarantine"> Quarantäne statt Löschen (wiederherstellbar)</label></div>
         <div class="row">
           <label for="maxp">Max. Parallelität</label>
           <input id="maxp" type="number" min="0" step="1" placeholder="auto" style="width:120px;background:#0a0f1a;border:1px solid #1e2a44;border-radius:8px;color:var(--text);padding:6px">
         </div>
+        <div class="row">
+          <label for="target_free">Mindestens freigeben (GB)</label>
+          <input id="target_free" type="number" min="0" step="0.5" placeholder="alles" style="width:120px;background:#0a0f1a;border:1px solid #1e2a44;border-radius:8px;color:var(--text);padding:6px">
+        </div>
         <div class="actions">
           <button id="run">Ausführen</button>
           <button class="btn-sec" id="health">Health</button>
@@ -458,9 +614,15 @@ const INDEX_HTML: &str = r#"<!doctype html>
       <p><strong>Verzeichnisse gelöscht:</strong> ${data.dirs_deleted}</p>
       <p><strong>Links entfernt:</strong> ${data.links_removed}</p>
       <p><strong>Bytes freigegeben:</strong> ${data.bytes_freed}</p>
+      <p><strong>Tatsächlich freigegeben (auf Datenträger):</strong> ${data.bytes_allocated} Bytes</p>
+      ${data.bytes_not_freed ? `<p><strong>Nicht wirklich freigegeben (Hardlinks):</strong> ${data.bytes_not_freed} Bytes</p>` : ''}
       <p><strong>Dauer:</strong> ${data.elapsed.toFixed(2)}s</p>
       <p><strong>Dry-Run:</strong> ${data.dry_run ? 'Ja' : 'Nein'}</p>
       <p><strong>Exakte Statistiken:</strong> ${data.exact_stats ? 'Ja' : 'Nein'}</p>
+      ${(data.failures||[]).length ? `<p><strong>Nicht bereinigt:</strong> ${data.failures.length} (Details in der Ausgabe)</p>` : ''}
+      ${(data.volumes||[]).map(v => `<p><strong>Freier Speicher ${v.volume}:</strong> ${v.freed >= 0 ? '+' : ''}${v.freed} Bytes</p>`).join('')}
+      ${data.budget ? `<p><strong>Genutzte Kategorien:</strong> ${data.budget.used.map(e => e.category).join(', ') || '–'}${data.budget.short ? ' (Ziel nicht erreichbar)' : ''}</p><p><strong>Unberührt:</strong> ${data.budget.untouched.map(e => e.category).join(', ') || '–'}</p>` : ''}
+      ${data.quarantine ? `<p><strong>Quarantäne:</strong> <code>${data.quarantine.path}</code> (Run ${data.quarantine.run_id})</p>` : ''}
       <details open>
         <summary><strong>Bereinigte Pfade:</strong> (${(data.cleaned_dirs||[]).length})</summary>
         <ul style="max-height:240px;overflow:auto;padding-left:20px">${list}</ul>
@@ -493,6 +655,8 @@ const INDEX_HTML: &str = r#"<!doctype html>
       allow_system_clean: $('#allow_system_clean').checked,
       prefetch: $('#prefetch').checked,
       max_parallelism: $('#maxp').value ? Number($('#maxp').value) : null,
+      quarantine: $('#quarantine').checked,
+      target_free_bytes: $('#target_free').value ? Math.round(Number($('#target_free').value) * 1024 ** 3) : null,
     };
     try {
       const res = await fetch('/api/run', { method:'POST', headers:{'Content-Type':'application/json','X-CSRF-Token': (CSRF_TOKEN||'')}, body: JSON.stringify(body) });
@@ -551,7 +715,10 @@ async fn delete_config(State(state): State<AppState>, headers: HeaderMap) -> Res
 async fn preview_targets_handler(State(state): State<AppState>, Json(req): Json<RunRequest>) -> Result<Json<TargetsPreview>, (StatusCode, String)> {
     // No CSRF required for read-only preview; could be tightened if desired
     let (cfg, overrides) = build_cfg_and_overrides(&state, &req).await;
-    let preview = preview_targets(&cfg, &overrides);
+    // Sizing walks every target; keep it off the async workers
+    let preview = tokio::task::spawn_blocking(move || preview_targets(&cfg, &overrides))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("preview failed: {}", e)))?;
     Ok(Json(preview))
 }
 
@@ -567,63 +734,44 @@ async fn run_cleaner_async(State(state): State<AppState>, headers: HeaderMap, Js
         OsRng.fill_bytes(&mut b);
         hex_encode(&b)
     };
-    let (cfg, overrides) = build_cfg_and_overrides(&state, &req).await;
+    let (cfg, mut overrides) = build_cfg_and_overrides(&state, &req).await;
+    let tracker = Arc::new(ProgressTracker::new(cfg.verbose && !cfg.quiet, cfg.dry_run));
+    overrides.observer = Some(tracker.clone());
+    let cancel = CancellationToken::new();
+    overrides.cancel = Some(cancel.clone());
     let started_at = now_unix();
     {
         let mut jobs = state.inner.jobs.lock().await;
-        jobs.insert(id.clone(), JobStatusData { id: id.clone(), status: "running".into(), started_at, finished_at: None, result: None, error: None });
+        jobs.insert(id.clone(), JobStatusData { id: id.clone(), status: "running".into(), started_at, finished_at: None, result: None, error: None, progress: None, tracker: Some(tracker.clone()), cancel: Some(cancel.clone()) });
     }
     let state2 = state.clone();
     let id_for_task = id.clone();
     tokio::spawn(async move {
-        let handle = tokio::task::spawn_blocking(move || { run_clean(&cfg, &overrides) });
-        let timeout_secs: u64 = std::env::var("ZENTIFY_WEB_RUN_TIMEOUT_SECS").ok().and_then(|s| s.parse::<u64>().ok()).unwrap_or(600);
-        let finished_at;
-        let mut status = "completed".to_string();
         let mut error: Option<String> = None;
-        let result: Option<RunResponse>;
-        let started_at_local = started_at;
-        match timeout(Duration::from_secs(timeout_secs), handle).await {
-            Ok(join_res) => match join_res {
-                Ok(summary) => {
-                    let mut stdout = String::new();
-                    if summary.dry_run {
-                        stdout.push_str(&format!(
-                            "Dry-run summary: would remove {} files, {} dirs, {} links; free approx {} ({} bytes) in {:?}.\n",
-                            summary.files_deleted, summary.dirs_deleted, summary.links_removed, format_bytes(summary.bytes_freed), summary.bytes_freed, summary.elapsed
-                        ));
-                    } else {
-                        stdout.push_str(&format!(
-                            "Summary: removed {} files, {} dirs, {} links; freed {} ({} bytes) in {:?}.\n",
-                            summary.files_deleted, summary.dirs_deleted, summary.links_removed, format_bytes(summary.bytes_freed), summary.bytes_freed, summary.elapsed
-                        ));
-                        if !summary.exact_stats { stdout.push_str("Note: Byte counts for directories are approximate (fast mode). Use --exact-stats for precise totals.\n"); }
-                    }
-                    let resp = RunResponse { ok: true, exit_code: 0, stdout, stderr: String::new(), files_deleted: summary.files_deleted, dirs_deleted: summary.dirs_deleted, links_removed: summary.links_removed, bytes_freed: summary.bytes_freed, elapsed: summary.elapsed.as_secs_f64(), dry_run: summary.dry_run, exact_stats: summary.exact_stats, cleaned_dirs: summary.cleaned_dirs };
-                    finished_at = now_unix();
-                    result = Some(resp.clone());
-                    push_history(&state2, Some(id_for_task.clone()), started_at_local, finished_at, resp).await;
-                }
-                Err(e) => {
-                    finished_at = now_unix();
-                    status = "failed".into();
-                    error = Some(format!("internal error: {}", e));
-                    result = None;
-                }
-            },
-            Err(_) => {
-                finished_at = now_unix();
-                status = "timeout".into();
-                error = Some(format!("cleaner timed out after {}s", timeout_secs));
-                result = None;
+        let mut result: Option<RunResponse> = None;
+        let status = match run_with_timeout(cfg, overrides, cancel).await {
+            Ok((summary, timed_out)) => {
+                let status = if timed_out { "timeout" } else if summary.cancelled { "cancelled" } else { "completed" };
+                if timed_out { error = Some(format!("cleaner timed out after {}s", run_timeout().as_secs())); }
+                let resp = response_from_summary(summary, tracker.take_log(), timed_out.then(run_timeout));
+                result = Some(resp.clone());
+                push_history(&state2, Some(id_for_task.clone()), started_at, now_unix(), resp).await;
+                status
             }
-        }
+            Err(e) => {
+                error = Some(format!("internal error: {}", e));
+                "failed"
+            }
+        };
+        let finished_at = now_unix();
         let mut jobs = state2.inner.jobs.lock().await;
         if let Some(entry) = jobs.get_mut(&id_for_task) {
-            entry.status = status;
+            entry.status = status.into();
             entry.finished_at = Some(finished_at);
             entry.result = result;
             entry.error = error;
+            entry.progress = entry.tracker.take().map(|t| t.snapshot());
+            entry.cancel = None;
         }
     });
 
@@ -633,7 +781,9 @@ async fn run_cleaner_async(State(state): State<AppState>, headers: HeaderMap, Js
 async fn job_status(State(state): State<AppState>, Path(id): Path<String>) -> Result<Json<JobStatusData>, (StatusCode, String)> {
     let jobs = state.inner.jobs.lock().await;
     if let Some(s) = jobs.get(&id) {
-        return Ok(Json(s.clone()));
+        let mut s = s.clone();
+        if let Some(t) = &s.tracker { s.progress = Some(t.snapshot()); }
+        return Ok(Json(s));
     }
     Err((StatusCode::NOT_FOUND, "job not found".into()))
 }
@@ -641,8 +791,22 @@ async fn job_status(State(state): State<AppState>, Path(id): Path<String>) -> Re
 async fn delete_job(State(state): State<AppState>, headers: HeaderMap, Path(id): Path<String>) -> Result<StatusCode, (StatusCode, String)> {
     csrf_check(&headers, &state)?;
     let mut jobs = state.inner.jobs.lock().await;
-    if jobs.remove(&id).is_some() {
+    if let Some(job) = jobs.remove(&id) {
+        // Forgetting a running job must not leave it deleting in the background
+        if let Some(c) = &job.cancel { c.cancel(); }
         return Ok(StatusCode::NO_CONTENT);
     }
     Err((StatusCode::NOT_FOUND, "job not found".into()))
 }
+
+async fn cancel_job(State(state): State<AppState>, headers: HeaderMap, Path(id): Path<String>) -> Result<StatusCode, (StatusCode, String)> {
+    csrf_check(&headers, &state)?;
+    let jobs = state.inner.jobs.lock().await;
+    match jobs.get(&id) {
+        Some(job) => {
+            if let Some(c) = &job.cancel { c.cancel(); }
+            Ok(StatusCode::ACCEPTED)
+        }
+        None => Err((StatusCode::NOT_FOUND, "job not found".into())),
+    }
+}
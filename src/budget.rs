@@ -0,0 +1,89 @@
+use std::cmp::Reverse;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::format_bytes;
+use crate::rules::Risk;
+
+/// Estimated size of one category in a budget run.
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryEstimate {
+    pub category: String,
+    pub priority: i32,
+    pub risk: Risk,
+    pub bytes: u64,
+    pub bytes_allocated: u64,
+}
+
+/// How a run with `target_free_bytes` picked its categories, as reported in `Summary::budget`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetReport {
+    pub target_free_bytes: u64,
+    /// Estimated on-disk size of the categories used
+    pub planned_bytes: u64,
+    /// Even all enabled categories together are estimated to free less than the goal
+    pub short: bool,
+    /// Categories cleaned, in ranking order
+    pub used: Vec<CategoryEstimate>,
+    /// Categories left alone because the goal was already met (or because they hold nothing)
+    pub untouched: Vec<CategoryEstimate>,
+}
+
+impl fmt::Display for BudgetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = |v: &[CategoryEstimate]| v.iter().map(|e| e.category.as_str()).collect::<Vec<_>>().join(", ");
+        write!(f, "Goal {}: used {} (est. {})", format_bytes(self.target_free_bytes), names(&self.used), format_bytes(self.planned_bytes))?;
+        if self.short { write!(f, ", short of the goal")?; }
+        if !self.untouched.is_empty() { write!(f, "; left untouched: {}", names(&self.untouched))?; }
+        Ok(())
+    }
+}
+
+/// Takes categories by priority, then risk, then size (largest first) until their allocated size reaches `goal`.
+pub(crate) fn plan(goal: u64, mut estimates: Vec<CategoryEstimate>) -> BudgetReport {
+    estimates.sort_by_key(|e| (e.priority, e.risk, Reverse(e.bytes_allocated)));
+    let mut planned = 0u64;
+    let (mut used, mut untouched) = (Vec::new(), Vec::new());
+    for e in estimates {
+        if planned >= goal || e.bytes_allocated == 0 {
+            untouched.push(e);
+            continue;
+        }
+        planned += e.bytes_allocated;
+        used.push(e);
+    }
+    BudgetReport { target_free_bytes: goal, planned_bytes: planned, short: planned < goal, used, untouched }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn est(category: &str, priority: i32, risk: Risk, size: u64) -> CategoryEstimate {
+        CategoryEstimate { category: category.into(), priority, risk, bytes: size, bytes_allocated: size }
+    }
+
+    #[test]
+    fn test_plan_prefers_low_risk_until_goal() {
+        let report = plan(150, vec![
+            est("thumbnails", 0, Risk::High, 1000),
+            est("browser_cache", 0, Risk::Medium, 100),
+            est("user_temp", 0, Risk::Low, 60),
+            est("crash_dumps", 0, Risk::Low, 80),
+            est("empty", 0, Risk::Low, 0),
+        ]);
+        let used: Vec<&str> = report.used.iter().map(|e| e.category.as_str()).collect();
+        assert_eq!(used, ["crash_dumps", "user_temp", "browser_cache"]);
+        assert_eq!(report.planned_bytes, 240);
+        assert!(!report.short);
+        assert_eq!(report.untouched.len(), 2);
+
+        // An explicit priority beats the risk order
+        let report = plan(500, vec![est("user_temp", 0, Risk::Low, 60), est("thumbnails", -1, Risk::High, 1000)]);
+        assert_eq!(report.used.len(), 1);
+        assert_eq!(report.used[0].category, "thumbnails");
+
+        assert!(plan(10_000, vec![est("user_temp", 0, Risk::Low, 60)]).short);
+    }
+}
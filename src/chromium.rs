@@ -0,0 +1,91 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::rules::{wildcard_match, Instance};
+
+/// Folder names Chromium gives profiles; used when `Local State` lists none.
+const PROFILE_DIRS: &[&str] = &["Default", "Profile *"];
+
+/// Profiles of the user data directory `data_dir`, as listed in `Local State` (`profile.info_cache`)
+/// found in `state_dir`. Without a readable list, folders named like profiles are taken instead.
+pub(crate) fn profiles(data_dir: &Path, state_dir: &Path) -> Vec<Instance> {
+    let listed = fs::read_to_string(state_dir.join("Local State")).ok()
+        .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+        .map(|v| listed_profiles(data_dir, &v))
+        .unwrap_or_default();
+    if !listed.is_empty() { return listed; }
+    let Ok(rd) = fs::read_dir(data_dir) else { return Vec::new() };
+    let mut out: Vec<Instance> = rd.flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|n| PROFILE_DIRS.iter().any(|p| wildcard_match(p, n)))
+        .map(|n| Instance { dir: data_dir.join(&n), name: n })
+        .collect();
+    out.sort_by(|a, b| a.dir.cmp(&b.dir));
+    out
+}
+
+fn listed_profiles(data_dir: &Path, state: &Value) -> Vec<Instance> {
+    let Some(cache) = state.pointer("/profile/info_cache").and_then(Value::as_object) else { return Vec::new() };
+    cache.iter()
+        // Keys are folder names inside the user data directory; anything else is not trusted
+        .filter(|(key, _)| is_plain_name(key))
+        .map(|(key, info)| {
+            let name = info.get("name").and_then(Value::as_str).filter(|n| !n.is_empty()).unwrap_or(key);
+            Instance { dir: data_dir.join(key), name: name.to_string() }
+        })
+        .filter(|p| p.dir.is_dir())
+        .collect()
+}
+
+/// Files an Electron app keeps at the top of its user data folder, which is its default profile.
+const ELECTRON_MARKERS: &[&str] = &["Local State", "Preferences"];
+
+/// Profiles of `dir` if it is an Electron app's user data folder: the folder itself and its
+/// `Partitions/*` (separate sessions, e.g. one per workspace). Nothing if the marker files are missing.
+pub(crate) fn app_profiles(dir: &Path) -> Vec<Instance> {
+    if !ELECTRON_MARKERS.iter().all(|m| dir.join(m).is_file()) { return Vec::new(); }
+    let app = dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let mut out = vec![Instance { dir: dir.to_path_buf(), name: app.clone() }];
+    if let Ok(rd) = fs::read_dir(dir.join("Partitions")) {
+        let mut partitions: Vec<Instance> = rd.flatten()
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+            .map(|e| Instance { name: format!("{} ({})", app, e.file_name().to_string_lossy()), dir: e.path() })
+            .collect();
+        partitions.sort_by(|a, b| a.dir.cmp(&b.dir));
+        out.extend(partitions);
+    }
+    out
+}
+
+fn is_plain_name(s: &str) -> bool { !s.is_empty() && s != "." && s != ".." && !s.contains(['/', '\\', ':']) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_from_local_state_or_folder_names() {
+        let root = std::env::temp_dir().join(format!("zentify-test-chromium-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for d in ["Default", "Profile 3", "Work", "Crashpad", "Safe Browsing"] {
+            fs::create_dir_all(root.join(d)).unwrap();
+        }
+        let state = r#"{"profile":{"info_cache":{
+            "Default":{"name":"Personal"},"Work":{"name":"Work (Acme)"},"Gone":{"name":"Deleted"},"..":{"name":"Escape"}}}}"#;
+        fs::write(root.join("Local State"), state).unwrap();
+        let mut found = profiles(&root, &root);
+        found.sort_by(|a, b| a.dir.cmp(&b.dir));
+        assert_eq!(found, vec![
+            Instance { dir: root.join("Default"), name: "Personal".into() },
+            Instance { dir: root.join("Work"), name: "Work (Acme)".into() },
+        ]);
+
+        // Local State kept elsewhere (e.g. ~/.config for ~/.cache) or missing: profile folder names
+        let names: Vec<String> = profiles(&root, &root.join("nowhere")).into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["Default", "Profile 3"]);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
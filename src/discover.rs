@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+use crate::platform::{self, KnownFolders};
+use crate::rules::{expand_pattern, KnownFolder, Risk, Rule, RuleCatalog, RuleKind, Scope};
+use crate::{compute_dir_stats, format_bytes, EntryFilter};
+
+/// Limits of a `discover` walk.
+#[derive(Debug, Clone, Copy)]
+pub struct DiscoverOptions {
+    /// Folder levels searched below each root
+    pub max_depth: usize,
+    /// Smaller folders are not reported
+    pub min_bytes: u64,
+}
+
+/// A cache-like folder no rule covers, with a rule entry that would clean it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Discovery {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub bytes_allocated: u64,
+    /// Days since anything inside was last modified
+    pub idle_days: u64,
+    /// Candidate rule (category `discovered`) to promote into a rule file
+    pub rule: Rule,
+}
+
+/// Where unknown caches are searched (base folder, sub path pattern).
+#[cfg(windows)]
+const ROOTS: &[(KnownFolder, &str)] = &[(KnownFolder::LocalAppData, ""), (KnownFolder::AppData, ""), (KnownFolder::LocalAppData, "Packages/*")];
+#[cfg(not(windows))]
+const ROOTS: &[(KnownFolder, &str)] = &[(KnownFolder::XdgConfig, "")];
+
+/// Folder names that suggest disposable data (compared ignoring case).
+const CACHE_NAMES: &[&str] = &["Cache", "Caches", "Code Cache", "GPUCache", "ShaderCache", "CrashDumps", "logs", "Temp", "tmp"];
+
+/// Cache-like folders below the search roots that no rule of `catalog` touches (enabled or not), largest first.
+/// Only folders inside the allowed prefixes are reported, so every candidate rule can actually run.
+pub fn discover_caches(catalog: &RuleCatalog, folders: &dyn KnownFolders, opts: &DiscoverOptions, threads: usize) -> Vec<Discovery> {
+    let known: Vec<PathBuf> = catalog.rules.iter().flat_map(|r| r.expand_in(folders)).collect();
+    let allowed: Vec<PathBuf> = platform::allowed_bases(folders, false).into_iter().filter(|b| b.is_dir()).collect();
+    let mut found: Vec<(KnownFolder, PathBuf, PathBuf)> = Vec::new();
+    for (base, sub) in ROOTS {
+        let Some(base_dir) = folders.folder(*base) else { continue };
+        for root in expand_pattern(&base_dir, sub).into_iter().filter(|p| p.is_dir()) {
+            search(&root, opts.max_depth, &known, &mut |p| {
+                if allowed.iter().any(|a| p.starts_with(a)) && !found.iter().any(|(_, _, f)| f == p) {
+                    found.push((*base, base_dir.clone(), p.to_path_buf()));
+                }
+            });
+        }
+    }
+
+    let now = SystemTime::now();
+    let mut out: Vec<Discovery> = found.into_iter().filter_map(|(base, base_dir, path)| {
+        let count = compute_dir_stats(&path, &EntryFilter::default(), threads);
+        if count.allocated < opts.min_bytes { return None; }
+        let newest = newest_modified(&path).unwrap_or(SystemTime::UNIX_EPOCH);
+        let idle_days = now.duration_since(newest).map(|d| d.as_secs() / 86400).unwrap_or(0);
+        let rule = candidate_rule(base, path.strip_prefix(&base_dir).ok()?, count.allocated, idle_days)?;
+        Some(Discovery { path, bytes: count.bytes, bytes_allocated: count.allocated, idle_days, rule })
+    }).collect();
+    // Largest first; among equal sizes the longest idle
+    out.sort_by(|a, b| b.bytes_allocated.cmp(&a.bytes_allocated).then(b.idle_days.cmp(&a.idle_days)));
+    out
+}
+
+/// Reports cache-like folders below `dir` that are not covered and hold nothing a rule matches.
+fn search(dir: &Path, depth: usize, known: &[PathBuf], report: &mut dyn FnMut(&Path)) {
+    if depth == 0 { return; }
+    let Ok(rd) = fs::read_dir(dir) else { return };
+    for e in rd.flatten() {
+        // Links are never followed
+        if !e.file_type().is_ok_and(|t| t.is_dir()) { continue; }
+        let p = e.path();
+        if known.iter().any(|k| p.starts_with(k)) { continue; }
+        let name = e.file_name().to_string_lossy().to_string();
+        let holds_known = known.iter().any(|k| k.starts_with(&p));
+        if !holds_known && CACHE_NAMES.iter().any(|c| c.eq_ignore_ascii_case(&name)) {
+            report(&p);
+        } else {
+            search(&p, depth - 1, known, report);
+        }
+    }
+}
+
+fn newest_modified(dir: &Path) -> Option<SystemTime> {
+    let mut newest: Option<SystemTime> = None;
+    for e in fs::read_dir(dir).ok()?.flatten() {
+        let Ok(md) = fs::symlink_metadata(e.path()) else { continue };
+        let t = if md.is_dir() { newest_modified(&e.path()) } else { md.modified().ok() };
+        newest = newest.max(t);
+    }
+    newest
+}
+
+/// Rule entry for `rel` below `base`; `None` if the path cannot be written as a rule pattern.
+fn candidate_rule(base: KnownFolder, rel: &Path, allocated: u64, idle_days: u64) -> Option<Rule> {
+    let path = rel.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect::<Vec<_>>().join("/");
+    if path.is_empty() || path.contains(['*', '?', '{', '}', ',']) { return None; }
+    let mut id = String::from("discovered_");
+    for c in path.chars() {
+        let c = if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' };
+        if c != '_' || !id.ends_with('_') { id.push(c); }
+    }
+    let id = id.trim_end_matches('_').to_string();
+    let leaf = rel.file_name().map(|n| n.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    let risk = if ["logs", "crashdumps", "temp", "tmp"].contains(&leaf.as_str()) { Risk::Low } else { Risk::Medium };
+    Some(Rule {
+        id,
+        category: "discovered".to_string(),
+        family: None,
+        base,
+        path,
+        env: None,
+        profiles: None,
+        instances: None,
+        kind: RuleKind::Dir,
+        scope: Scope::User,
+        default_enabled: true,
+        risk,
+        min_age_hours: None,
+        keep: None,
+        description: format!("Found by discover ({} on disk, idle {} days)", format_bytes(allocated), idle_days),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VirtualRoot;
+
+    #[test]
+    fn test_discover_reports_uncovered_cache_dirs_as_rules() {
+        let root = std::env::temp_dir().join(format!("zentify-test-discover-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let folders = VirtualRoot::new(&root);
+        let base = if cfg!(windows) { KnownFolder::LocalAppData } else { KnownFolder::XdgConfig };
+        let dir = folders.folder(base).unwrap();
+        // Below an app folder, as Linux allows only those in ~/.config
+        for (p, size) in [("Code/logs/main.log", 300), ("Code/User/settings.json", 10), ("Code/a/b/c/logs/old.log", 50), ("Code/Cache/k", 40)] {
+            let p = dir.join(p);
+            fs::create_dir_all(p.parent().unwrap()).unwrap();
+            fs::write(p, vec![0u8; size]).unwrap();
+        }
+        let catalog: RuleCatalog = serde_json::from_value(serde_json::json!({ "rules": [
+            { "id": "known", "category": "k", "base": base, "path": "Code/Cache", "scope": "user" }
+        ]})).unwrap();
+
+        let opts = DiscoverOptions { max_depth: 4, min_bytes: 0 };
+        let found = discover_caches(&catalog, &folders, &opts, 2);
+        // Code/Cache has a rule, Code/a/b/c/logs is below the depth limit
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, dir.join("Code/logs"));
+        assert_eq!((found[0].rule.id.as_str(), found[0].rule.path.as_str()), ("discovered_code_logs", "Code/logs"));
+        assert_eq!(found[0].bytes, 300);
+        assert!(discover_caches(&catalog, &folders, &DiscoverOptions { min_bytes: u64::MAX, ..opts }, 2).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+/// Why an entry was not cleaned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureReason {
+    /// In use by another process (sharing/lock violation)
+    Locked,
+    /// Denied by ACLs or file permissions
+    AccessDenied,
+    /// Any other I/O error while removing or quarantining
+    RemoveFailed,
+    /// Quarantine mode was requested but its folder could not be created
+    QuarantineUnavailable,
+    /// Skipped: protected system directory
+    SensitiveDir,
+    /// Skipped: filesystem root
+    FilesystemRoot,
+    /// Skipped: not below one of the allowed base folders
+    OutsideAllowedPrefixes,
+    /// Skipped: the target contains the quarantine folder
+    ContainsQuarantine,
+    /// Skipped: excluded in the config or inside a folder marked with `.zentify-keep`
+    Excluded,
+}
+
+impl FailureReason {
+    /// Safety skips are expected; everything else is a real failure.
+    pub fn is_skip(self) -> bool {
+        matches!(self, Self::SensitiveDir | Self::FilesystemRoot | Self::OutsideAllowedPrefixes | Self::ContainsQuarantine | Self::Excluded)
+    }
+
+    fn classify(e: &io::Error) -> Self {
+        match e.raw_os_error() {
+            // ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION
+            Some(32) | Some(33) if cfg!(windows) => return Self::Locked,
+            // EBUSY / ETXTBSY
+            Some(16) | Some(26) if cfg!(unix) => return Self::Locked,
+            _ => {}
+        }
+        match e.kind() {
+            io::ErrorKind::PermissionDenied => Self::AccessDenied,
+            _ => Self::RemoveFailed,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Locked => "in use by another process",
+            Self::AccessDenied => "access denied",
+            Self::RemoveFailed => "could not be removed",
+            Self::QuarantineUnavailable => "quarantine folder unavailable",
+            Self::SensitiveDir => "skipped (protected system directory)",
+            Self::FilesystemRoot => "skipped (filesystem root)",
+            Self::OutsideAllowedPrefixes => "skipped (outside allowed locations)",
+            Self::ContainsQuarantine => "skipped (contains the quarantine folder)",
+            Self::Excluded => "skipped (excluded or marked to keep)",
+        }
+    }
+}
+
+/// An entry that a run could not clean, as reported in `Summary::failures`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanFailure {
+    pub path: String,
+    pub reason: FailureReason,
+    /// `io::ErrorKind` of the underlying error, e.g. `PermissionDenied`
+    pub error_kind: Option<String>,
+    pub raw_os_error: Option<i32>,
+    pub message: Option<String>,
+}
+
+impl CleanFailure {
+    pub fn from_io(path: &Path, e: &io::Error) -> Self {
+        Self::with_reason(path, FailureReason::classify(e), e)
+    }
+
+    pub(crate) fn with_reason(path: &Path, reason: FailureReason, e: &io::Error) -> Self {
+        Self {
+            path: path.to_string_lossy().to_string(),
+            reason,
+            error_kind: Some(format!("{:?}", e.kind())),
+            raw_os_error: e.raw_os_error(),
+            message: Some(e.to_string()),
+        }
+    }
+
+    pub fn skipped(path: &Path, reason: FailureReason) -> Self {
+        Self { path: path.to_string_lossy().to_string(), reason, error_kind: None, raw_os_error: None, message: None }
+    }
+}
+
+impl fmt::Display for CleanFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason.describe())?;
+        if let Some(m) = &self.message { write!(f, " ({})", m)?; }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_io_errors() {
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        assert_eq!(CleanFailure::from_io(Path::new("x"), &denied).reason, FailureReason::AccessDenied);
+        let busy = io::Error::from_raw_os_error(if cfg!(windows) { 32 } else { 16 });
+        let f = CleanFailure::from_io(Path::new("x"), &busy);
+        assert_eq!(f.reason, FailureReason::Locked);
+        assert!(f.raw_os_error.is_some());
+        assert!(FailureReason::SensitiveDir.is_skip() && !FailureReason::Locked.is_skip());
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::rules::{wildcard_match, Instance};
+
+/// Folder names Gecko gives profiles (`Profiles/<salt>.<name>` on Windows, `<salt>.<name>` elsewhere);
+/// used when no profile list is found.
+const PROFILE_DIRS: &[&str] = &["Profiles/*", "*.*"];
+
+/// Profiles listed in `profiles.ini` and `installs.ini` in `state_dir` (the roaming folder), mapped into
+/// `store` (the folder holding the caches). A profile inside `state_dir` keeps its relative path in `store`;
+/// one elsewhere (a custom `Path=`, holding a `prefs.js`) keeps its caches in the profile folder itself, as Gecko does.
+pub(crate) fn profiles(store: &Path, state_dir: &Path) -> Vec<Instance> {
+    let mut listed: Vec<(String, Option<String>)> = Vec::new();
+    if let Ok(text) = fs::read_to_string(state_dir.join("profiles.ini")) {
+        for (section, keys) in parse_ini(&text) {
+            let get = |k: &str| get(&keys, k);
+            if section.starts_with("Profile") {
+                let Some(path) = get("Path") else { continue };
+                listed.push((profile_path(&path, get("IsRelative")), get("Name")));
+            } else if let Some(default) = get("Default").filter(|_| section.starts_with("Install")) {
+                listed.push((profile_path(&default, get("IsRelative")), None));
+            }
+        }
+    }
+    if let Ok(text) = fs::read_to_string(state_dir.join("installs.ini")) {
+        listed.extend(parse_ini(&text).into_iter()
+            .filter_map(|(_, keys)| get(&keys, "Default").map(|v| (profile_path(&v, get(&keys, "IsRelative")), None))));
+    }
+
+    let mut out: Vec<Instance> = Vec::new();
+    for (path, name) in listed.into_iter().filter(|(p, _)| !p.is_empty()) {
+        let roaming = state_dir.join(&path);
+        let dir = match roaming.strip_prefix(state_dir) {
+            Ok(rel) => store.join(rel),
+            // A folder elsewhere only counts if it holds a profile; runs are allowed into it for its caches
+            Err(_) if roaming.join("prefs.js").is_file() => roaming,
+            Err(_) => continue,
+        };
+        let name = name.unwrap_or_else(|| dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+        if dir.is_dir() && !out.iter().any(|p| p.dir == dir) { out.push(Instance { dir, name }); }
+    }
+    if !out.is_empty() { return out; }
+
+    // No list: folders named like profiles
+    for pattern in PROFILE_DIRS {
+        let (parent, leaf) = match pattern.rsplit_once('/') {
+            Some((parent, leaf)) => (store.join(parent), leaf),
+            None => (store.to_path_buf(), *pattern),
+        };
+        let Ok(rd) = fs::read_dir(&parent) else { continue };
+        for e in rd.flatten().filter(|e| e.file_type().is_ok_and(|t| t.is_dir())) {
+            let name = e.file_name().to_string_lossy().to_string();
+            if wildcard_match(leaf, &name) { out.push(Instance { dir: e.path(), name }); }
+        }
+    }
+    out.sort_by(|a, b| a.dir.cmp(&b.dir));
+    out
+}
+
+/// `path` as its `IsRelative` flag says: plainly relative (no `..`) with `1`, absolute with `0`, either without the flag.
+/// Anything else is empty.
+fn profile_path(path: &str, is_relative: Option<String>) -> String {
+    let p = Path::new(path);
+    let plain = !p.has_root() && p.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    let ok = match is_relative.as_deref().map(str::trim) {
+        Some("1") => plain,
+        Some("0") => p.is_absolute(),
+        _ => plain || p.is_absolute(),
+    };
+    if ok { path.to_string() } else { String::new() }
+}
+
+/// Value of `key` in a section, ignoring the key's case as Gecko does.
+fn get(keys: &[(String, String)], key: &str) -> Option<String> {
+    keys.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.clone())
+}
+
+/// Sections of an INI file with their `key=value` pairs, in file order.
+fn parse_ini(text: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut out: Vec<(String, Vec<(String, String)>)> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with([';', '#']) { continue; }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            out.push((section.trim().to_string(), Vec::new()));
+        } else if let (Some((key, value)), Some((_, keys))) = (line.split_once('='), out.last_mut()) {
+            keys.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_profiles_ini_maps_roaming_profiles_to_cache_dirs() {
+        let root = std::env::temp_dir().join(format!("zentify-test-gecko-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let (roaming, local, custom) = (root.join("roaming"), root.join("local"), root.join("elsewhere/work"));
+        for d in [roaming.join("Profiles/a1.default-release"), local.join("Profiles/a1.default-release"), local.join("Profiles/b2.dev"), custom.clone()] {
+            fs::create_dir_all(d).unwrap();
+        }
+        fs::write(custom.join("prefs.js"), b"").unwrap();
+        let ini = format!(
+            "[General]\nStartWithLastProfile=1\n\n[Profile0]\nName=default-release\nIsRelative=1\nPath=Profiles/a1.default-release\n\n\
+             [Profile1]\nName=Work\nIsRelative=0\nPath={}\n\n[Profile2]\nName=Escape\nIsRelative=1\nPath=../../etc\n\n\
+             [Profile3]\nName=Misflagged\nIsRelative=0\nPath=Profiles/b2.dev\n\n[Profile4]\nName=Rooted\nIsRelative=1\nPath={}\n",
+            custom.display(), custom.display()
+        );
+        fs::write(roaming.join("profiles.ini"), ini).unwrap();
+        fs::write(roaming.join("installs.ini"), "[308046B0AF4A39CB]\ndefault=Profiles/b2.dev\nLocked=1\n").unwrap();
+
+        let found: Vec<(PathBuf, String)> = profiles(&local, &roaming).into_iter().map(|p| (p.dir, p.name)).collect();
+        assert_eq!(found, vec![
+            (local.join("Profiles/a1.default-release"), "default-release".to_string()),
+            (custom.clone(), "Work".to_string()),
+            (local.join("Profiles/b2.dev"), "b2.dev".to_string()),
+        ]);
+
+        // No profile list: folders named like profiles
+        let names: Vec<String> = profiles(&local, &root.join("nowhere")).into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["a1.default-release", "b2.dev"]);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use crate::rules::wildcard_match;
+
+/// File that protects the folder holding it, with everything below, from cleaning.
+pub(crate) const KEEP_MARKER: &str = ".zentify-keep";
+
+/// Compiled `exclude` patterns of the config. A pattern without a separator matches entries by name anywhere
+/// (`*.lic`); one with a separator matches full paths segment by segment, `**` standing for any number of folders.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Exclusions {
+    patterns: Arc<Vec<Vec<String>>>,
+    /// Exact folders left to other targets
+    paths: Arc<Vec<PathBuf>>,
+}
+
+impl Exclusions {
+    pub(crate) fn new(patterns: &[String]) -> Self {
+        let compiled = patterns.iter()
+            .map(|p| p.split(['/', '\\']).filter(|s| !s.is_empty()).map(str::to_string).collect::<Vec<_>>())
+            .filter(|segments| !segments.is_empty())
+            .collect();
+        Self { patterns: Arc::new(compiled), paths: Arc::default() }
+    }
+
+    /// The same patterns, also excluding exactly `paths`.
+    pub(crate) fn with_paths(self, paths: &[PathBuf]) -> Self {
+        if paths.is_empty() { return self; }
+        Self { paths: Arc::new(paths.to_vec()), ..self }
+    }
+
+    /// Whether the config excludes `path` (and so everything below it).
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        if self.paths.iter().any(|p| p == path) { return true; }
+        if self.patterns.is_empty() { return false; }
+        let segments: Vec<String> = path.components().filter_map(|c| match c {
+            Component::Prefix(p) => Some(p.as_os_str().to_string_lossy().to_string()),
+            Component::Normal(n) => Some(n.to_string_lossy().to_string()),
+            _ => None,
+        }).collect();
+        let Some(name) = segments.last() else { return false };
+        self.patterns.iter().any(|pattern| match pattern.as_slice() {
+            [single] if single != "**" => wildcard_match(single, name),
+            _ => segments_match(pattern, &segments),
+        })
+    }
+}
+
+fn segments_match(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((first, rest)) => path.split_first().is_some_and(|(seg, tail)| wildcard_match(first, seg) && segments_match(rest, tail)),
+    }
+}
+
+/// Whether `dir` holds the keep marker.
+pub(crate) fn has_marker(dir: &Path) -> bool { fs::symlink_metadata(dir.join(KEEP_MARKER)).is_ok() }
+
+/// Whether `path` lies in (or is) an excluded or marked folder, so it must be left alone entirely.
+pub(crate) fn protects(path: &Path, exclude: &Exclusions) -> bool {
+    path.ancestors().any(|a| exclude.matches(a) || has_marker(a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exclusions_match_names_and_paths() {
+        let ex = Exclusions::new(&["*.lic".to_string(), "/work/**/keep-*".to_string(), "/work/tool/state".to_string()]);
+        assert!(ex.matches(Path::new("/tmp/a/license.lic")));
+        assert!(ex.matches(Path::new("/work/keep-me")));
+        assert!(ex.matches(Path::new("/work/a/b/keep-1")));
+        assert!(ex.matches(Path::new("/work/tool/state")));
+        assert!(!ex.matches(Path::new("/work/tool/state/inner")) && protects(Path::new("/work/tool/state/inner"), &ex));
+        assert!(!ex.matches(Path::new("/other/keep-me")));
+        assert!(!Exclusions::default().matches(Path::new("/tmp/a.lic")));
+    }
+}
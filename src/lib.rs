@@ -1,8 +1,10 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex, atomic::{AtomicUsize, AtomicU64, Ordering}};
-use std::thread;
-use std::time::{Duration, Instant};
+use std::io;
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU64, Ordering}};
+use std::time::{Duration, Instant, SystemTime};
+#[cfg(windows)]
 use std::process::Command;
 
 use serde::{Deserialize, Serialize};
@@ -17,59 +19,142 @@ use windows_sys::Win32::Storage::FileSystem::{MoveFileExW, MOVEFILE_DELAY_UNTIL_
 #[cfg(windows)]
 use windows_sys::Win32::Security::{CheckTokenMembership, CreateWellKnownSid, SECURITY_MAX_SID_SIZE, WinBuiltinAdministratorsSid};
 
+pub mod artifacts;
+pub mod budget;
+mod chromium;
+pub mod discover;
+pub mod failure;
+mod gecko;
+mod keep;
+mod links;
+pub mod observer;
+pub mod platform;
+pub mod quarantine;
+pub mod rules;
+mod trim;
+pub mod volume;
+mod walker;
+
+use links::{hardlink_id, LinkTracker};
+use volume::VolumeSampler;
+use walker::{Job, TreeWalk, Worker};
+pub use artifacts::{find_artifacts, Artifact, ArtifactSweep};
+pub use budget::{BudgetReport, CategoryEstimate};
+pub use discover::{discover_caches, DiscoverOptions, Discovery};
+pub use failure::{CleanFailure, FailureReason};
+pub use observer::{CleanEvent, CleanObserver, EntryKind, Phase};
+pub use platform::{KnownFolders, SystemFolders, VirtualRoot};
+pub use quarantine::{Quarantine, QuarantineInfo};
+pub use rules::{EnvOverride, KnownFolder, ProfileLayout, ProfileSet, Risk, Rule, RuleCatalog, RuleKind, Scope};
+pub use volume::VolumeDelta;
+
 // Public API types
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct Categories {
-    #[serde(default = "true_bool")] pub windows_temp: bool,
-    #[serde(default = "true_bool")] pub user_temp: bool,
-    #[serde(default = "true_bool")] pub browser_cache: bool,
-    #[serde(default = "true_bool")] pub windows_update: bool,
-    #[serde(default = "true_bool")] pub delivery_optimization: bool,
-    #[serde(default = "true_bool")] pub crash_dumps: bool,
-    #[serde(default = "true_bool")] pub error_reports: bool,
-    #[serde(default = "true_bool")] pub thumbnails: bool,
-    #[serde(default = "true_bool")] pub directx_cache: bool,
-    #[serde(default = "true_bool")] pub temp_internet_files: bool,
-    #[serde(default = "false_bool")] pub prefetch: bool,
-    // Newer categories
-    #[serde(default = "true_bool")] pub defender_cache: bool,
-    #[serde(default = "true_bool")] pub office_cache: bool,
-    #[serde(default = "true_bool")] pub aspnet_temp: bool,
-    #[serde(default = "true_bool")] pub teams_cache: bool,
-    #[serde(default = "true_bool")] pub modern_apps_cache: bool,
-    #[serde(default = "true_bool")] pub java_cache: bool,
-    #[serde(default = "true_bool")] pub adobe_cache: bool,
-    #[serde(default = "true_bool")] pub wmp_cache: bool,
-    #[serde(default = "true_bool")] pub widgets_cache: bool,
-}
 
-pub fn preview_targets(cfg: &Config, overrides: &RunOverrides) -> TargetsPreview {
-    // Determine effective categories similar to run_clean
-    let mut cats = cfg.effective_categories();
-    if overrides.allow_system {
-        cats.prefetch = true;
+/// Category and rule toggles as written in the config (`"browser_cache": false`, `"chrome_cache": true`).
+/// A rule is enabled by its own id, then by its category, then by its family (e.g. `"dev_caches": true`),
+/// then by the catalog's `default_enabled`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Categories(pub BTreeMap<String, bool>);
+
+impl Categories {
+    pub fn get(&self, key: &str) -> Option<bool> { self.0.get(key).copied() }
+
+    pub fn set(&mut self, key: &str, enabled: bool) { self.0.insert(key.to_string(), enabled); }
+
+    pub fn rule_enabled(&self, rule: &Rule) -> bool {
+        self.get(&rule.id)
+            .or_else(|| self.get(&rule.category))
+            .or_else(|| rule.family.as_deref().and_then(|f| self.get(f)))
+            .unwrap_or(rule.default_enabled)
     }
-    if let Some(p) = overrides.prefetch {
-        if p { cats.prefetch = true; }
+
+    /// Fills in every catalog category that is not set explicitly.
+    pub fn with_defaults(&self, catalog: &RuleCatalog) -> Categories {
+        let mut out = self.clone();
+        for cat in catalog.categories() {
+            if out.get(cat).is_none() {
+                let on = catalog.rules.iter().filter(|r| r.category == cat)
+                    .any(|r| r.family.as_deref().and_then(|f| self.get(f)).unwrap_or(r.default_enabled));
+                out.set(cat, on);
+            }
+        }
+        out
     }
+}
 
-    let mut dirs = candidate_dirs(&cats, overrides.allow_system);
-    let mut files = candidate_files(&cats, overrides.allow_system);
+pub fn preview_targets(cfg: &Config, overrides: &RunOverrides) -> TargetsPreview {
+    // Determine effective categories similar to run_clean
+    let cats = run_categories(cfg, overrides);
+    let folders = overrides.folders();
+    let catalog = RuleCatalog::load_from(folders);
+    let (mut dirs, mut files) = run_targets(cfg, &catalog, &cats, folders, overrides);
+    let bases = run_bases(folders, overrides, &dirs, &files);
 
     // Apply same filters
-    dirs.retain(|p| p.is_dir());
-    retain_allowed_paths(&mut dirs, overrides.allow_system);
+    dirs.retain(|t| t.path.is_dir());
+    retain_allowed_paths(&mut dirs, &bases);
+    // The run skips these as well
+    dirs.retain(|t| t.path.parent().is_some() && !is_sensitive_dir(folders, &t.path));
     dedup_paths(&mut dirs);
+    files.retain(|t| t.path.is_file());
+    retain_allowed_paths(&mut files, &bases);
+    dedup_paths(&mut files);
+    let exclude = keep::Exclusions::new(&cfg.exclude);
+    dirs.retain(|t| !keep::protects(&t.path, &exclude));
+    files.retain(|t| !keep::protects(&t.path, &exclude));
+
+    // Instances are sized whether the policy selects them or not, so they can be picked from the preview
+    let left_out = left_out_paths(cfg, &dirs, &files);
+    let kept = |t: &Target| !left_out.iter().any(|p| t.path.starts_with(p));
+    let mut instances: BTreeMap<PathBuf, InstancePreview> = BTreeMap::new();
+    let mut total = TreeCount::default();
+    // Targets under a retention or trim policy only count if something in them would go
+    dirs.retain(|t| {
+        let count = dir_target_size(cfg, t, worker_count(overrides));
+        add_instance_size(&mut instances, t, &count, kept(t));
+        if !kept(t) { return false; }
+        let selective = EntryFilter::for_target(cfg, t).is_active() || cfg.policy_for(&t.category).trim_keep().is_some();
+        if selective && count.files + count.dirs == 0 { return false; }
+        total.bytes += count.bytes;
+        total.allocated += count.allocated;
+        total.unfreed += count.unfreed;
+        true
+    });
+
+    files.retain(|t| {
+        let Some(count) = file_target_size(cfg, t) else { return false };
+        add_instance_size(&mut instances, t, &count, kept(t));
+        if !kept(t) { return false; }
+        total.bytes += count.bytes;
+        total.allocated += count.allocated;
+        true
+    });
 
-    files.retain(|p| p.is_file());
-    retain_allowed_paths(&mut files, overrides.allow_system);
-    files.sort();
-    files.dedup();
+    let profiles = dirs.iter().chain(&files)
+        .filter_map(|t| t.instance.as_ref().map(|i| (t.path.to_string_lossy().to_string(), i.name.clone())))
+        .collect();
+    let target_dirs = dirs.into_iter().map(|t| t.path.to_string_lossy().to_string()).collect();
+    let target_files = files.into_iter().map(|t| t.path.to_string_lossy().to_string()).collect();
 
-    let target_dirs = dirs.into_iter().map(|p| p.to_string_lossy().to_string()).collect();
-    let target_files = files.into_iter().map(|p| p.to_string_lossy().to_string()).collect();
+    let instances = instances.into_values().collect();
+
+    TargetsPreview { target_dirs, target_files, profiles, instances, bytes: total.bytes, bytes_allocated: total.allocated, bytes_not_freed: total.unfreed }
+}
 
-    TargetsPreview { target_dirs, target_files }
+fn add_instance_size(instances: &mut BTreeMap<PathBuf, InstancePreview>, t: &Target, count: &TreeCount, selected: bool) {
+    let Some(i) = &t.instance else { return };
+    let e = instances.entry(i.dir.clone()).or_insert_with(|| InstancePreview {
+        category: t.category.clone(),
+        id: i.id(),
+        name: i.name.clone(),
+        path: i.dir.to_string_lossy().to_string(),
+        selected,
+        bytes: 0,
+        bytes_allocated: 0,
+    });
+    e.bytes += count.bytes;
+    e.bytes_allocated += count.allocated;
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -79,6 +164,138 @@ pub struct Config {
     #[serde(default)] pub quiet: bool,
     #[serde(default)] pub exact_stats: bool,
     #[serde(default)] pub categories: Option<Categories>,
+    /// Per-category cleaning policies, keyed by category name (`"default"` applies to all others)
+    #[serde(default)] pub policies: BTreeMap<String, CategoryPolicy>,
+    /// Move targets into a per-run quarantine folder instead of deleting them
+    #[serde(default)] pub quarantine: bool,
+    /// Quarantine location (defaults to `%LOCALAPPDATA%/Zentify/Quarantine`); targets on other volumes use `.zentify-quarantine` at their top
+    #[serde(default)] pub quarantine_dir: Option<String>,
+    /// Entries never cleaned: name patterns (`*.lic`) or full path patterns (`**` spans folders)
+    #[serde(default)] pub exclude: Vec<String>,
+    /// Extra locations cleaned like catalog rules
+    #[serde(default)] pub custom_targets: Vec<CustomTarget>,
+}
+
+/// Options that refine how a single category is cleaned.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CategoryPolicy {
+    /// Only delete entries last modified/accessed more than this many hours ago
+    #[serde(default)] pub min_age_hours: Option<u64>,
+    /// Rank in budget runs (`target_free_bytes`); lower goes first, ties are broken by risk and size
+    #[serde(default)] pub priority: i32,
+    #[serde(default)] pub action: CleanAction,
+    /// With `action: trim`, how much each target of the category keeps (on-disk size, MiB)
+    #[serde(default)] pub keep_mb: Option<u64>,
+    /// Only these instances (browser profiles, app packages) by id or name; `*`/`?` allowed. Empty means all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] pub include_instances: Vec<String>,
+    /// Instances left alone, by id or name; `*`/`?` allowed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] pub exclude_instances: Vec<String>,
+}
+
+/// What a run does with the targets of a category.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanAction {
+    /// Remove everything (subject to `min_age_hours`)
+    #[default]
+    Purge,
+    /// Keep the most recently used files up to `keep_mb` and evict the oldest
+    Trim,
+}
+
+/// A location of the user's own, defined in the config instead of a rule file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CustomTarget {
+    /// Rule id for toggles and policies (default `custom_<n>`, counting from 1)
+    #[serde(default)] pub id: Option<String>,
+    /// Category it is toggled with (default `custom`)
+    #[serde(default)] pub category: Option<String>,
+    /// Folder starting with a known-folder variable (`%LOCALAPPDATA%/Foo/Cache`, `~/.cache/foo`); `*`, `?` and `{a,b}` allowed
+    pub path: String,
+    #[serde(default)] pub mode: CustomMode,
+    /// With `mode: glob`, the files to remove, relative to `path` (e.g. `*.log`, `*/dumps/*.dmp`)
+    #[serde(default)] pub glob: Option<String>,
+    /// With `mode: age`, entries untouched for this long are removed
+    #[serde(default)] pub max_age_hours: Option<u64>,
+    #[serde(default)] pub scope: Scope,
+}
+
+/// What a custom target removes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomMode {
+    /// Everything in the folder; the folder itself stays for the app that writes to it
+    #[default]
+    Purge,
+    /// Files matching `glob`
+    Glob,
+    /// Entries older than `max_age_hours`
+    Age,
+    /// Browser caches in every profile of a Chromium user data folder (a browser started with `--user-data-dir`)
+    Chromium,
+}
+
+/// Profile caches a `chromium` custom target cleans, as in the catalog's Chromium browser rules.
+const CHROMIUM_PROFILE_CACHES: &str = "{Cache,Code Cache,GPUCache,ShaderCache,DawnCache,GrShaderCache,Media Cache,Service Worker/CacheStorage,Application Cache,Network/Cache}";
+
+impl CustomTarget {
+    /// The entry as a rule; `Err` says why it cannot be used.
+    fn to_rule(&self, index: usize) -> Result<Rule, String> {
+        let (base, path) = rules::split_template(&self.path)
+            .ok_or("path must start with a known-folder variable such as %LOCALAPPDATA% and stay below it")?;
+        if path.is_empty() { return Err("path must name a folder below the variable".into()); }
+        let (path, kind, min_age_hours) = match self.mode {
+            CustomMode::Purge | CustomMode::Chromium => (path, RuleKind::Dir, None),
+            CustomMode::Glob => {
+                let glob = self.glob.as_deref().map(str::trim).filter(|g| !g.is_empty()).ok_or("mode glob needs a glob")?;
+                if glob.split(['/', '\\']).any(|s| s == "..") { return Err("glob must stay inside path".into()); }
+                (format!("{}/{}", path, glob), RuleKind::File, None)
+            }
+            CustomMode::Age => {
+                let hours = self.max_age_hours.filter(|h| *h > 0).ok_or("mode age needs max_age_hours")?;
+                (path, RuleKind::Dir, Some(hours))
+            }
+        };
+        Ok(Rule {
+            id: self.id.clone().unwrap_or_else(|| format!("custom_{}", index + 1)),
+            category: self.category.clone().unwrap_or_else(|| "custom".to_string()),
+            family: None,
+            base,
+            path,
+            env: None,
+            profiles: (self.mode == CustomMode::Chromium).then(|| ProfileSet {
+                layout: ProfileLayout::Chromium,
+                path: CHROMIUM_PROFILE_CACHES.to_string(),
+                state_base: None,
+                state_path: None,
+            }),
+            instances: None,
+            kind,
+            scope: self.scope,
+            default_enabled: true,
+            risk: Risk::default(),
+            min_age_hours,
+            keep: None,
+            description: format!("Custom target {}", self.path),
+        })
+    }
+}
+
+impl CategoryPolicy {
+    /// Whether `instance` passes the include and exclude lists.
+    fn selects(&self, instance: &rules::Instance) -> bool {
+        let id = instance.id();
+        let listed = |list: &[String]| list.iter().any(|p| rules::wildcard_match(p, &id) || rules::wildcard_match(p, &instance.name));
+        !listed(&self.exclude_instances) && (self.include_instances.is_empty() || listed(&self.include_instances))
+    }
+
+    /// Bytes to keep per target when trimming, `None` when purging.
+    fn trim_keep(&self) -> Option<u64> {
+        self.keep_mb.filter(|_| self.action == CleanAction::Trim).map(|mb| mb.saturating_mul(1 << 20))
+    }
+
+    /// A trim without `keep_mb`: its targets are skipped rather than purged.
+    fn incomplete(&self) -> bool { self.action == CleanAction::Trim && self.keep_mb.is_none() }
 }
 
 impl Default for Config {
@@ -88,65 +305,66 @@ impl Default for Config {
             verbose: false,
             quiet: false,
             exact_stats: false,
-            categories: Some(Categories {
-                windows_temp: true,
-                user_temp: true,
-                browser_cache: true,
-                windows_update: true,
-                delivery_optimization: true,
-                crash_dumps: true,
-                error_reports: true,
-                thumbnails: true,
-                directx_cache: true,
-                temp_internet_files: true,
-                prefetch: false,
-                defender_cache: true,
-                office_cache: true,
-                aspnet_temp: true,
-                teams_cache: true,
-                modern_apps_cache: true,
-                java_cache: true,
-                adobe_cache: true,
-                wmp_cache: true,
-                widgets_cache: true,
-            }),
+            policies: BTreeMap::new(),
+            quarantine: false,
+            quarantine_dir: None,
+            exclude: Vec::new(),
+            custom_targets: Vec::new(),
+            categories: Some(Categories::default()),
         }
     }
 }
 
 impl Config {
+    /// Configured toggles completed with the rule catalog defaults.
     pub fn effective_categories(&self) -> Categories {
-        self.categories.clone().unwrap_or(Categories {
-            windows_temp: true,
-            user_temp: true,
-            browser_cache: true,
-            windows_update: true,
-            delivery_optimization: true,
-            crash_dumps: true,
-            error_reports: true,
-            thumbnails: true,
-            directx_cache: true,
-            temp_internet_files: true,
-            prefetch: false,
-            defender_cache: true,
-            office_cache: true,
-            aspnet_temp: true,
-            teams_cache: true,
-            modern_apps_cache: true,
-            java_cache: true,
-            adobe_cache: true,
-            wmp_cache: true,
-            widgets_cache: true,
-        })
+        let mut catalog = RuleCatalog::load();
+        catalog.extend(RuleCatalog { rules: self.custom_rules() });
+        self.categories.clone().unwrap_or_default().with_defaults(&catalog)
+    }
+
+    /// `custom_targets` as rules; unusable entries are logged and left out.
+    pub fn custom_rules(&self) -> Vec<Rule> {
+        self.custom_targets.iter().enumerate().filter_map(|(i, c)| match c.to_rule(i) {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                log::warn!("ignoring custom target {}: {}", c.path, e);
+                None
+            }
+        }).collect()
+    }
+
+    /// Policy for `category`, falling back to the `"default"` entry and then to no restrictions.
+    pub fn policy_for(&self, category: &str) -> CategoryPolicy {
+        self.policies.get(category)
+            .or_else(|| self.policies.get("default"))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Retention of `category`: its own policy, then the rule's default, then the `"default"` policy.
+    fn min_age_hours(&self, category: &str, rule_default: Option<u64>) -> Option<u64> {
+        self.policies.get(category).and_then(|p| p.min_age_hours)
+            .or(rule_default)
+            .or_else(|| self.policies.get("default").and_then(|p| p.min_age_hours))
+    }
+
+    /// Folder holding the quarantine runs.
+    pub fn quarantine_root(&self) -> Option<PathBuf> { self.quarantine_root_in(&SystemFolders) }
+
+    /// Like [`quarantine_root`](Self::quarantine_root), with the default location taken from `folders`.
+    pub fn quarantine_root_in(&self, folders: &dyn KnownFolders) -> Option<PathBuf> {
+        if let Some(d) = &self.quarantine_dir { return Some(PathBuf::from(d)); }
+        folders.data_dir().map(|p| p.join(if cfg!(windows) { "Quarantine" } else { "quarantine" }))
     }
 }
 
-pub fn load_config() -> Config {
+pub fn load_config() -> Config { load_config_from(&SystemFolders) }
+
+/// Loads the first `config.json` found in `folders.config_dirs()`.
+pub fn load_config_from(folders: &dyn KnownFolders) -> Config {
     // Search order: CWD/.zentify/config.json, %PROGRAMDATA%/Zentify/config.json, %APPDATA%/Zentify/config.json
-    let mut paths: Vec<PathBuf> = Vec::new();
-    if let Ok(cwd) = std::env::current_dir() { paths.push(cwd.join(".zentify/config.json")); }
-    if let Ok(pd) = std::env::var("ProgramData") { paths.push(Path::new(&pd).join("Zentify/config.json")); }
-    if let Ok(ad) = std::env::var("APPDATA") { paths.push(Path::new(&ad).join("Zentify/config.json")); }
+    let paths: Vec<PathBuf> = folders.config_dirs().into_iter().map(|d| d.join("config.json")).collect();
 
     for p in paths {
         if p.is_file() {
@@ -155,6 +373,9 @@ pub fn load_config() -> Config {
                     // Merge with defaults
                     let def = Config::default();
                     if c.categories.is_none() { c.categories = def.categories; }
+                    for (category, _) in c.policies.iter().filter(|(_, p)| p.incomplete()) {
+                        log::warn!("policy {}: action trim needs keep_mb; its targets are skipped", category);
+                    }
                     return c;
                 }
             }
@@ -169,19 +390,41 @@ struct Stats {
     dirs_deleted: AtomicU64,
     links_removed: AtomicU64,
     bytes_freed: AtomicU64,
+    bytes_allocated: AtomicU64,
+    /// Hard-linked data that targets settled on their own (quarantine moves) and that stays reachable elsewhere
+    bytes_not_freed: AtomicU64,
+    /// Multiply-linked files removed by the run, booked once all their links are gone
+    hardlinks: LinkTracker,
     cleaned_dirs: Mutex<Vec<String>>,
+    failures: Mutex<Vec<CleanFailure>>,
 }
 
 impl Stats {
     fn add_files(&self, n: u64) { self.files_deleted.fetch_add(n, Ordering::Relaxed); }
     fn add_dirs(&self, n: u64) { self.dirs_deleted.fetch_add(n, Ordering::Relaxed); }
     fn add_links(&self, n: u64) { self.links_removed.fetch_add(n, Ordering::Relaxed); }
-    fn add_bytes(&self, n: u64) { self.bytes_freed.fetch_add(n, Ordering::Relaxed); }
+    fn add_bytes(&self, n: u64, allocated: u64) {
+        self.bytes_freed.fetch_add(n, Ordering::Relaxed);
+        self.bytes_allocated.fetch_add(allocated, Ordering::Relaxed);
+    }
+    fn add_count(&self, c: &TreeCount) {
+        self.add_bytes(c.bytes, c.allocated);
+        self.bytes_not_freed.fetch_add(c.unfreed, Ordering::Relaxed);
+        self.add_files(c.files);
+        self.add_dirs(c.dirs);
+        self.add_links(c.links);
+    }
     fn add_cleaned_dir(&self, p: &Path) {
         if let Ok(mut v) = self.cleaned_dirs.lock() {
             v.push(p.to_string_lossy().to_string());
         }
     }
+    fn add_failure(&self, f: CleanFailure) {
+        if let Ok(mut v) = self.failures.lock() { v.push(f); }
+    }
+    fn take_failures(&self) -> Vec<CleanFailure> {
+        self.failures.lock().map(|mut v| std::mem::take(&mut *v)).unwrap_or_default()
+    }
     fn snapshot(&self) -> (u64, u64, u64, u64) {
         (
             self.files_deleted.load(Ordering::Relaxed),
@@ -198,10 +441,39 @@ impl Stats {
     }
 }
 
+#[derive(Clone, Default)]
 pub struct RunOverrides {
     pub allow_system: bool,
     pub prefetch: Option<bool>,
     pub max_parallelism: Option<usize>,
+    /// Receives progress events while `run_clean` works
+    pub observer: Option<Arc<dyn CleanObserver>>,
+    /// Stops the run early when cancelled; the summary then covers only what was done so far
+    pub cancel: Option<CancellationToken>,
+    /// Space to recover: size all targets first, then clean only the best-ranked categories that reach it
+    pub target_free_bytes: Option<u64>,
+    /// Where the well-known folders are; defaults to the real machine ([`SystemFolders`])
+    pub folders: Option<Arc<dyn KnownFolders>>,
+    /// Clean build output of idle projects below these workspace roots instead of the rule catalog
+    pub sweep: Option<ArtifactSweep>,
+}
+
+impl RunOverrides {
+    fn folders(&self) -> &dyn KnownFolders { self.folders.as_deref().unwrap_or(&SystemFolders) }
+}
+
+/// Cheap, cloneable flag for stopping an in-flight `run_clean` from another thread.
+/// Workers check it before each target and between entries of the per-entry walks;
+/// a delete or rename already in progress (including a fast-mode `remove_dir_all`) finishes first.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn cancel(&self) { self.0.store(true, Ordering::Relaxed); }
+
+    pub fn is_cancelled(&self) -> bool { self.0.load(Ordering::Relaxed) }
 }
 
 pub struct Summary {
@@ -209,202 +481,389 @@ pub struct Summary {
     pub dirs_deleted: u64,
     pub links_removed: u64,
     pub bytes_freed: u64,
+    /// Space on disk those bytes occupied (cluster slack, sparse and compressed files); what the volume actually gets back
+    pub bytes_allocated: u64,
+    /// Hard-linked files whose data stays reachable through links outside the run; not part of `bytes_freed`
+    pub bytes_not_freed: u64,
     pub elapsed: Duration,
     pub dry_run: bool,
     pub exact_stats: bool,
     pub cleaned_dirs: Vec<String>,
+    /// Where this run's entries were moved to, in quarantine mode
+    pub quarantine: Option<QuarantineInfo>,
+    /// Categories picked for a `target_free_bytes` run and those left alone
+    pub budget: Option<BudgetReport>,
+    /// Free-space change per affected volume, measured around the run (empty in dry-run)
+    pub volumes: Vec<VolumeDelta>,
+    /// The run was stopped through its cancellation token before all targets were processed
+    pub cancelled: bool,
+    /// Entries that could not be cleaned, and targets skipped by the safety checks
+    pub failures: Vec<CleanFailure>,
+}
+
+impl Summary {
+    /// Process exit code for this run: 0 success, 3 some entries could not be cleaned, 130 cancelled.
+    pub fn exit_code(&self) -> i32 {
+        if self.cancelled { return 130; }
+        if self.failures.iter().any(|f| !f.reason.is_skip()) { return 3; }
+        0
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct TargetsPreview {
     pub target_dirs: Vec<String>,
     pub target_files: Vec<String>,
+    /// Display names of the instances (browser profiles, app packages) targets belong to, keyed by target path
+    pub profiles: BTreeMap<String, String>,
+    /// Every instance of the enabled multi-instance rules with what cleaning it would free, including left-out ones
+    pub instances: Vec<InstancePreview>,
+    /// Logical size of what a run with the same settings would remove
+    pub bytes: u64,
+    /// Allocated size on disk of the same entries
+    pub bytes_allocated: u64,
+    /// Hard-linked files that would lose a path but keep their data through other links
+    pub bytes_not_freed: u64,
 }
 
-pub fn run_clean(cfg: &Config, overrides: &RunOverrides) -> Summary {
-    let start = Instant::now();
+/// A browser profile or app package in a preview.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstancePreview {
+    pub category: String,
+    /// Folder name, as used in `include_instances` and `exclude_instances`
+    pub id: String,
+    /// Profile display name, else the id
+    pub name: String,
+    pub path: String,
+    /// Whether the category's policy keeps the instance in a run
+    pub selected: bool,
+    pub bytes: u64,
+    pub bytes_allocated: u64,
+}
 
-    // Determine effective categories for this run
-    let mut cats = cfg.effective_categories();
+/// What cleaning a directory target would remove, honoring its category's retention and trim policy.
+fn dir_target_size(cfg: &Config, t: &Target, threads: usize) -> TreeCount {
+    let filter = EntryFilter::for_target(cfg, t);
+    match cfg.policy_for(&t.category).trim_keep() {
+        Some(keep) if t.path.is_dir() => trim::trim_stats(&t.path, &filter, keep, threads),
+        _ => compute_dir_stats(&t.path, &filter, threads),
+    }
+}
+
+/// Size of a file target, or `None` if it is missing or its category's filter keeps it.
+fn file_target_size(cfg: &Config, t: &Target) -> Option<TreeCount> {
+    let md = fs::metadata(&t.path).ok().filter(|m| m.is_file())?;
+    if !EntryFilter::for_target(cfg, t).allows(&md) { return None; }
+    Some(TreeCount { bytes: md.len(), allocated: allocated_size(&t.path, &md), files: 1, ..TreeCount::default() })
+}
+
+/// Sizes every target and picks the categories a budget run cleans.
+fn plan_budget(cfg: &Config, catalog: &RuleCatalog, goal: u64, dirs: &[Target], files: &[Target], threads: usize) -> BudgetReport {
+    let mut sizes: BTreeMap<&str, TreeCount> = BTreeMap::new();
+    for t in dirs {
+        let count = dir_target_size(cfg, t, threads);
+        let e = sizes.entry(&t.category).or_default();
+        e.bytes += count.bytes;
+        e.allocated += count.allocated;
+    }
+    for t in files {
+        let count = file_target_size(cfg, t).unwrap_or_default();
+        let e = sizes.entry(&t.category).or_default();
+        e.bytes += count.bytes;
+        e.allocated += count.allocated;
+    }
+    let estimates = sizes.into_iter().map(|(category, c)| CategoryEstimate {
+        category: category.to_string(),
+        priority: cfg.policy_for(category).priority,
+        risk: catalog.category_risk(category),
+        bytes: c.bytes,
+        bytes_allocated: c.allocated,
+    }).collect();
+    budget::plan(goal, estimates)
+}
+
+/// Configured toggles adjusted for the run overrides.
+fn run_categories(cfg: &Config, overrides: &RunOverrides) -> Categories {
+    let mut cats = cfg.categories.clone().unwrap_or_default();
     if overrides.allow_system {
         // As Administrator, enable prefetch cleanup for more aggressive cleaning
-        cats.prefetch = true;
+        cats.set("prefetch", true);
     }
     if let Some(p) = overrides.prefetch {
-        if p { cats.prefetch = true; }
-    }
-
-    // Build aggressive list of temp/cache targets
-    let mut targets = candidate_dirs(&cats, overrides.allow_system);
-    let mut file_targets = candidate_files(&cats, overrides.allow_system);
-
-    // Chromium-based browsers caches (Chrome, Edge, Brave, Vivaldi, Opera GX)
-    if cats.browser_cache {
-        if let Ok(base) = std::env::var("LOCALAPPDATA") {
-            push_chromium_caches(&mut targets, Path::new(&base).join("Google/Chrome"));
-            push_chromium_caches(&mut targets, Path::new(&base).join("Microsoft/Edge"));
-            push_chromium_caches(&mut targets, Path::new(&base).join("BraveSoftware/Brave-Browser"));
-            push_chromium_caches(&mut targets, Path::new(&base).join("Vivaldi/Vivaldi"));
-            // Opera variants
-            push_chromium_caches(&mut targets, Path::new(&base).join("Opera Software/Opera GX Stable"));
-            push_chromium_caches(&mut targets, Path::new(&base).join("Opera Software/Opera Stable"));
-        }
-    }
-
-    // Firefox caches
-    if cats.browser_cache {
-        if let Ok(base) = std::env::var("LOCALAPPDATA") {
-            let profiles = Path::new(&base).join("Mozilla/Firefox/Profiles");
-            if profiles.is_dir() {
-                if let Ok(entries) = fs::read_dir(&profiles) {
-                    for e in entries.flatten() {
-                        let p = e.path();
-                        if p.is_dir() {
-                            targets.push(p.join("cache2"));
-                            targets.push(p.join("startupCache"));
-                        }
-                    }
-                }
+        if p { cats.set("prefetch", true); }
+    }
+    cats
+}
+
+pub fn run_clean(cfg: &Config, overrides: &RunOverrides) -> Summary {
+    let start = Instant::now();
+    let observer = overrides.observer.as_deref();
+    if let Some(o) = observer { o.on_event(&CleanEvent::PhaseChanged(Phase::Scanning)); }
+
+    // Determine effective categories for this run
+    let cats = run_categories(cfg, overrides);
+
+    // Build aggressive list of temp/cache targets from the rule catalog
+    let folders = overrides.folders();
+    let catalog = RuleCatalog::load_from(folders);
+    let (mut targets, mut file_targets) = run_targets(cfg, &catalog, &cats, folders, overrides);
+    retain_selected_instances(cfg, &mut targets, &mut file_targets);
+    let bases = run_bases(folders, overrides, &targets, &file_targets);
+
+    // Quarantine mode: set up this run's folder; never clean a target that would contain it
+    let mut quarantine_error: Option<CleanFailure> = None;
+    let quarantine = if cfg.quarantine && !cfg.dry_run {
+        let root = cfg.quarantine_root_in(folders);
+        let res = match &root {
+            Some(root) => Quarantine::create(root),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no quarantine location available")),
+        };
+        match res {
+            Ok(q) => Some(q),
+            Err(e) => {
+                log::error!("cannot create quarantine folder: {}", e);
+                let at = root.unwrap_or_default();
+                quarantine_error = Some(CleanFailure::with_reason(&at, FailureReason::QuarantineUnavailable, &e));
+                None
             }
         }
+    } else {
+        None
+    };
+
+    let stats = Stats::default();
+    let ctx = RunCtx { cfg, folders, stats: &stats, quarantine: quarantine.as_ref(), observer, cancel: overrides.cancel.as_ref() };
+    if let Some(f) = quarantine_error {
+        // Refuse to fall back to permanent deletion
+        stats.add_failure(f);
+        targets.clear();
+        file_targets.clear();
     }
 
     // Delete directories aggressively (with limited concurrency)
     // Filter to existing directories first to avoid overhead on nonexistent paths
-    targets.retain(|p| p.is_dir());
+    targets.retain(|t| t.path.is_dir());
     // Extra safety: keep only paths under allowed prefixes
-    retain_allowed_paths(&mut targets, overrides.allow_system);
+    for t in retain_allowed_paths(&mut targets, &bases) {
+        ctx.skipped(&t.path, FailureReason::OutsideAllowedPrefixes);
+    }
     dedup_paths(&mut targets);
-    let avail = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
-    let mut concurrency = avail.min(8);
-    if let Some(n) = overrides.max_parallelism { concurrency = n.clamp(1, avail); }
-
-    let targets_arc = Arc::new(targets);
-    let index = Arc::new(AtomicUsize::new(0));
-    let stats = Arc::new(Stats::default());
-    let mut handles = Vec::new();
-    for _ in 0..concurrency {
-        let t = Arc::clone(&targets_arc);
-        let idx = Arc::clone(&index);
-        let cfg_local = cfg.clone();
-        let stats_local = Arc::clone(&stats);
-        handles.push(thread::spawn(move || {
-            loop {
-                let i = idx.fetch_add(1, Ordering::Relaxed);
-                if i >= t.len() { break; }
-                let d = &t[i];
-                fast_clean_dir(d, &cfg_local, &*stats_local);
-            }
-        }));
+
+    // Excluded entries and folders marked to keep stay, with everything in them
+    let exclude = keep::Exclusions::new(&cfg.exclude);
+    for list in [&mut targets, &mut file_targets] {
+        list.retain(|t| {
+            let kept = t.path.exists() && keep::protects(&t.path, &exclude);
+            if kept { ctx.skipped(&t.path, FailureReason::Excluded); }
+            !kept
+        });
+    }
+
+    if let Some(q) = &quarantine {
+        let folders = q.folders_for(targets.iter().chain(&file_targets).map(|t| t.path.as_path()));
+        for list in [&mut targets, &mut file_targets] {
+            list.retain(|t| {
+                // Folders on other volumes may not exist yet: their nearest existing ancestor stands in for them
+                let inside = folders.iter().any(|d| d.ancestors().find(|a| a.exists()).is_some_and(|a| is_under(a, &t.path)));
+                if inside { ctx.skipped(&t.path, FailureReason::ContainsQuarantine); }
+                !inside
+            });
+        }
     }
-    for h in handles { let _ = h.join(); }
+
+    // Budget run: size everything up front and keep only the categories needed to reach the goal
+    let budget = overrides.target_free_bytes.map(|goal| {
+        let report = plan_budget(cfg, &catalog, goal, &targets, &file_targets, worker_count(overrides));
+        let used = |t: &Target| report.used.iter().any(|e| e.category == t.category);
+        targets.retain(used);
+        file_targets.retain(used);
+        report
+    });
+
+    // A sweep deletes nothing whose marker file went away since it was found
+    if overrides.sweep.is_some() { targets.retain(|t| artifacts::is_artifact_dir(&t.path)); }
+
+    // Fast mode cannot count bytes; the free space on each affected volume can
+    let volumes = (!cfg.dry_run).then(|| VolumeSampler::sample(targets.iter().chain(&file_targets).map(|t| t.path.as_path())));
+
+    ctx.emit(CleanEvent::PhaseChanged(Phase::Directories));
+    // One work-stealing pool for the whole run: targets are jobs, and walking a large target queues
+    // its subdirectories as further jobs, so idle workers help with a single huge cache
+    let jobs = (0..targets.len()).map(Job::Target).collect();
+    walker::run(worker_count(overrides), jobs, |job, w| run_job(job, &targets, &ctx, w));
 
     // If we're going to delete Explorer's thumbnail/icon caches, stop Explorer first to unlock files
     #[cfg(windows)]
     let mut explorer_stopped = false;
     #[cfg(windows)]
     {
-        if cats.thumbnails && file_targets.iter().any(|p| p.to_string_lossy().to_ascii_lowercase().contains("microsoft\\windows\\explorer")) {
-            if stop_explorer(cfg) {
+        if !ctx.cancelled() && file_targets.iter().any(|t| t.category == "thumbnails" && t.path.to_string_lossy().to_ascii_lowercase().contains("microsoft\\windows\\explorer")) {
+            if stop_explorer() {
                 explorer_stopped = true;
             }
         }
     }
 
     // Delete specific files (e.g., thumbnail caches)
-    file_targets.retain(|p| p.is_file());
+    ctx.emit(CleanEvent::PhaseChanged(Phase::Files));
+    file_targets.retain(|t| t.path.is_file());
     // Extra safety: keep only files under allowed prefixes
-    retain_allowed_paths(&mut file_targets, overrides.allow_system);
-    file_targets.sort();
-    file_targets.dedup();
-    for f in file_targets.drain(..) {
-        if cfg.dry_run {
-            let size = fs::metadata(&f).map(|m| m.len()).unwrap_or(0);
-            if cfg.verbose && !cfg.quiet { println!("[dry-run] Would remove file: {} ({} bytes)", f.display(), size); }
-            stats.add_bytes(size);
-            stats.add_files(1);
-            if let Some(parent) = f.parent() { stats.add_cleaned_dir(parent); }
-            continue;
-        }
-        set_writable(&f);
-        let size = fs::metadata(&f).map(|m| m.len()).unwrap_or(0);
-        if fs::remove_file(&f).is_ok() {
-            if cfg.verbose && !cfg.quiet { println!("Removed file: {} ({} bytes)", f.display(), size); }
-            stats.add_bytes(size);
-            stats.add_files(1);
-            if let Some(parent) = f.parent() { stats.add_cleaned_dir(parent); }
-        } else {
-            // As a fallback on Windows, schedule deletion on next reboot (locked files like Explorer caches)
-            #[cfg(windows)]
-            {
-                if schedule_delete_on_reboot(&f) {
-                    if cfg.verbose && !cfg.quiet { println!("Scheduled for deletion on reboot: {} ({} bytes)", f.display(), size); }
-                    // Count bytes and files as they will be freed on reboot
-                    stats.add_bytes(size);
-                    stats.add_files(1);
-                    if let Some(parent) = f.parent() { stats.add_cleaned_dir(parent); }
-                }
-            }
-        }
+    for t in retain_allowed_paths(&mut file_targets, &bases) {
+        ctx.skipped(&t.path, FailureReason::OutsideAllowedPrefixes);
+    }
+    dedup_paths(&mut file_targets);
+    for t in file_targets.drain(..) {
+        if ctx.cancelled() { break; }
+        let filter = EntryFilter::for_target(cfg, &t);
+        let f = t.path;
+        if !fs::metadata(&f).map(|m| filter.allows(&m)).unwrap_or(false) { continue; }
+        ctx.emit(CleanEvent::TargetStarted { path: &f, category: &t.category });
+        clean_file_target(&f, &ctx);
+        ctx.emit(CleanEvent::TargetFinished { path: &f, category: &t.category });
     }
 
     // Restart Explorer if we stopped it
     #[cfg(windows)]
     {
         if explorer_stopped {
-            start_explorer(cfg);
+            start_explorer();
         }
     }
+    let volumes = volumes.map(VolumeSampler::finish).unwrap_or_default();
+    ctx.emit(CleanEvent::PhaseChanged(Phase::Finished));
 
+    // Hard-linked data counts once, and only if the run removed every link
+    let linked = stats.hardlinks.totals();
+    stats.add_bytes(linked.freed_bytes, linked.freed_allocated);
     let (files, dirs, links, bytes) = stats.snapshot();
     let mut cleaned_dirs = stats.get_cleaned_dirs();
     // Also include top-level targets that were entirely removed (best-effort):
     // If a target dir no longer exists after cleaning, add it.
-    for d in targets_arc.iter() {
-        if !d.exists() {
-            cleaned_dirs.push(d.to_string_lossy().to_string());
+    for t in targets.iter() {
+        if !t.path.exists() {
+            cleaned_dirs.push(t.path.to_string_lossy().to_string());
         }
     }
     cleaned_dirs.sort();
     cleaned_dirs.dedup();
     let elapsed = start.elapsed();
+    let cancelled = ctx.cancelled();
 
     Summary {
         files_deleted: files,
         dirs_deleted: dirs,
         links_removed: links,
         bytes_freed: bytes,
+        bytes_allocated: stats.bytes_allocated.load(Ordering::Relaxed),
+        bytes_not_freed: linked.unfreed_bytes + stats.bytes_not_freed.load(Ordering::Relaxed),
         elapsed,
         dry_run: cfg.dry_run,
         exact_stats: cfg.exact_stats,
         cleaned_dirs,
+        quarantine: quarantine.map(|q| q.info()),
+        volumes,
+        budget,
+        cancelled,
+        failures: stats.take_failures(),
+    }
+}
+
+/// Shared state of one cleaning run, borrowed by every worker.
+struct RunCtx<'a> {
+    cfg: &'a Config,
+    folders: &'a dyn KnownFolders,
+    stats: &'a Stats,
+    quarantine: Option<&'a Quarantine>,
+    observer: Option<&'a dyn CleanObserver>,
+    cancel: Option<&'a CancellationToken>,
+}
+
+impl RunCtx<'_> {
+    fn cancelled(&self) -> bool { self.cancel.is_some_and(|c| c.is_cancelled()) }
+
+    fn emit(&self, event: CleanEvent) {
+        if let Some(o) = self.observer { o.on_event(&event); }
+    }
+
+    fn removed(&self, path: &Path, kind: EntryKind, files: u64, dirs: u64, bytes: u64) {
+        self.emit(CleanEvent::EntryRemoved { path, kind, files, dirs, bytes });
+    }
+
+    fn failed(&self, path: &Path, error: &io::Error) {
+        // Vanished meanwhile (e.g. the owning application cleaned up itself): nothing left to do
+        if error.kind() == io::ErrorKind::NotFound { return; }
+        self.emit(CleanEvent::EntryFailed { path, error });
+        self.stats.add_failure(CleanFailure::from_io(path, error));
+    }
+
+    fn skipped(&self, path: &Path, reason: FailureReason) {
+        self.emit(CleanEvent::TargetSkipped { path, reason });
+        self.stats.add_failure(CleanFailure::skipped(path, reason));
+    }
+
+    /// Deletes `p` (recursively for directories and links to them) or, in quarantine mode,
+    /// moves it into the run's quarantine folder. `size` is only recorded in the manifest.
+    fn dispose(&self, p: &Path, is_dir: bool, size: u64) -> io::Result<()> {
+        match self.quarantine {
+            Some(q) => q.stash(p, is_dir, size),
+            None if is_dir => fs::remove_dir_all(p),
+            None => fs::remove_file(p),
+        }
+    }
+
+    /// Removes a link (junction/symlink) itself, never its target.
+    fn dispose_link(&self, p: &Path) -> io::Result<()> {
+        match self.quarantine {
+            Some(q) => q.stash(p, false, 0),
+            None => fs::remove_dir(p).or_else(|_| fs::remove_file(p)),
+        }
+    }
+}
+
+/// A cleaning target together with the category that selected it.
+#[derive(Debug, Clone)]
+struct Target {
+    path: PathBuf,
+    category: String,
+    /// The rule's retention default
+    min_age_hours: Option<u64>,
+    /// Browser profile or app package the target belongs to
+    instance: Option<rules::Instance>,
+    /// Targets nested inside this one that follow their own settings; cleaned around
+    keep: Vec<PathBuf>,
+    /// Only emptied, never removed itself (custom targets)
+    keep_root: bool,
+}
+
+impl Target {
+    /// Whether the category's policy keeps the target's instance in the run.
+    fn selected(&self, cfg: &Config) -> bool {
+        self.instance.as_ref().map_or(true, |i| cfg.policy_for(&self.category).selects(i))
+    }
+}
+
+/// Paths of the targets whose instance is left out; nothing at or below them is cleaned.
+fn left_out_paths(cfg: &Config, dirs: &[Target], files: &[Target]) -> Vec<PathBuf> {
+    dirs.iter().chain(files).filter(|t| !t.selected(cfg)).map(|t| t.path.clone()).collect()
+}
+
+fn retain_selected_instances(cfg: &Config, dirs: &mut Vec<Target>, files: &mut Vec<Target>) {
+    let left_out = left_out_paths(cfg, dirs, files);
+    for list in [dirs, files] {
+        list.retain(|t| !left_out.iter().any(|p| t.path.starts_with(p)));
     }
 }
 
-fn dedup_paths(v: &mut Vec<PathBuf>) {
-    v.retain(|p| !p.as_os_str().is_empty());
-    v.sort();
-    v.dedup();
+fn dedup_paths(v: &mut Vec<Target>) {
+    v.retain(|t| !t.path.as_os_str().is_empty());
+    v.sort_by(|a, b| a.path.cmp(&b.path));
+    v.dedup_by(|a, b| a.path == b.path);
 }
 
 /// Build a conservative list of allowed root prefixes under which we will operate.
 /// This is an extra safety net on top of explicit path selection and sensitive-dir checks.
-fn allowed_prefixes(allow_system: bool) -> Vec<PathBuf> {
-    let mut bases: Vec<PathBuf> = Vec::new();
-    // User-scoped bases
-    bases.push(std::env::temp_dir());
-    if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
-        bases.push(PathBuf::from(localappdata));
-    }
-    if let Ok(appdata) = std::env::var("APPDATA") {
-        bases.push(PathBuf::from(appdata));
-    }
-    // System-scoped bases (only if explicitly allowed)
-    if allow_system {
-        if let Ok(windir) = std::env::var("WINDIR") { bases.push(PathBuf::from(windir)); }
-        if let Ok(systemroot) = std::env::var("SystemRoot") { bases.push(PathBuf::from(systemroot)); }
-        if let Ok(programdata) = std::env::var("ProgramData") { bases.push(PathBuf::from(programdata)); }
-    }
+fn allowed_prefixes(folders: &dyn KnownFolders, allow_system: bool) -> Vec<PathBuf> {
+    let mut bases = platform::allowed_bases(folders, allow_system);
     // Normalize: keep only absolute, existing directories, dedup
     bases.retain(|p| p.is_dir());
     bases.sort();
@@ -421,390 +880,468 @@ fn is_under(path: &Path, base: &Path) -> bool {
     }
 }
 
-fn retain_allowed_paths(v: &mut Vec<PathBuf>, allow_system: bool) {
-    let bases = allowed_prefixes(allow_system);
-    if bases.is_empty() { return; }
-    v.retain(|p| bases.iter().any(|b| is_under(p, b)));
+/// Keeps only targets below one of `bases` and returns the dropped ones.
+fn retain_allowed_paths(v: &mut Vec<Target>, bases: &[PathBuf]) -> Vec<Target> {
+    if bases.is_empty() { return Vec::new(); }
+    let (keep, dropped) = v.drain(..).partition(|t| bases.iter().any(|b| is_under(&t.path, b)));
+    *v = keep;
+    dropped
 }
 
-fn candidate_dirs(cats: &Categories, allow_system: bool) -> Vec<PathBuf> {
-    let mut v: Vec<PathBuf> = Vec::new();
-
-    // User temp
-    if cats.user_temp {
-        v.push(std::env::temp_dir());
-        if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
-            v.push(Path::new(&localappdata).join("Temp"));
-            if cats.directx_cache { v.push(Path::new(&localappdata).join("D3DSCache")); }
-            if cats.temp_internet_files {
-                v.push(Path::new(&localappdata).join("Microsoft/Windows/INetCache"));
-                // Legacy WebCache (ESE) used by IE/Legacy Edge/Explorer
-                v.push(Path::new(&localappdata).join("Microsoft/Windows/WebCache"));
-            }
-            if cats.crash_dumps {
-                v.push(Path::new(&localappdata).join("CrashDumps"));
-            }
-            // User-level WER
-            if cats.error_reports {
-                v.push(Path::new(&localappdata).join("Microsoft/Windows/WER/ReportQueue"));
-                v.push(Path::new(&localappdata).join("Microsoft/Windows/WER/ReportArchive"));
-                v.push(Path::new(&localappdata).join("Microsoft/Windows/WER/Temp"));
-            }
-            // Windows Widgets (WebExperience) cache
-            if cats.widgets_cache {
-                let widgets_pkg = Path::new(&localappdata).join("Packages/MicrosoftWindows.Client.WebExperience_cw5n1h2txyewy");
-                v.push(widgets_pkg.join("LocalCache"));
-                v.push(widgets_pkg.join("TempState"));
-            }
-            // Teams (Classic) caches
-            if cats.teams_cache {
-                v.push(Path::new(&localappdata).join("Packages/MSTeams_8wekyb3d8bbwe/LocalCache")); // New Teams (UWP)
-            }
-            // Office Document Cache
-            if cats.office_cache {
-                v.push(Path::new(&localappdata).join("Microsoft/Office/16.0/OfficeFileCache"));
-            }
-            // NVIDIA shader caches (tie to directx_cache category)
-            if cats.directx_cache {
-                v.push(Path::new(&localappdata).join("NVIDIA/GLCache"));
-                v.push(Path::new(&localappdata).join("NVIDIA/DXCache"));
-            }
-            // Windows Media Player cache (best-effort)
-            if cats.wmp_cache {
-                v.push(Path::new(&localappdata).join("Microsoft/Media Player/Cache"));
-            }
-            // Java cache
-            if cats.java_cache {
-                v.push(Path::new(&localappdata).join("Sun/Java/Deployment/cache"));
-            }
-            // Adobe media caches (common locations)
-            if cats.adobe_cache {
-                v.push(Path::new(&localappdata).join("Adobe/Common/Media Cache"));
-                v.push(Path::new(&localappdata).join("Adobe/Common/Media Cache Files"));
+/// Directory and file targets of every enabled rule that exists on this machine.
+/// A broad rule (e.g. everything in `~/.cache`) is cleaned around what other rules match inside it, so
+/// switching off the specific rule's category keeps its paths. A folder profiles were found in (a browser's
+/// user data, an app's data folder) is kept whole with the top-level entry of the broad target that holds it,
+/// and a broad target that is such a folder itself is left to the profile rules.
+/// Where several rules match the same path, the one with fewer wildcards wins, then the later one (user rule
+/// files come after the built-in catalog); a path matched by a switched-off rule is left alone.
+fn candidate_targets(catalog: &RuleCatalog, cats: &Categories, folders: &dyn KnownFolders, allow_system: bool) -> (Vec<Target>, Vec<Target>) {
+    // System-level only if allowed explicitly
+    let eligible = |r: &Rule| (r.scope == Scope::User || allow_system) && cats.rule_enabled(r);
+    // A browser's user data looks like an Electron app's (e.g. Opera keeps its profile at the top), so it is never taken for one
+    let browser_roots: Vec<PathBuf> = catalog.rules.iter().flat_map(|r| r.browser_roots_in(folders)).collect();
+    let matched: Vec<(&Rule, Vec<rules::InstancePath>, Vec<PathBuf>)> = catalog.rules.iter()
+        .map(|r| {
+            let (mut paths, stores) = r.expand_stores_in(folders);
+            if r.profiles.as_ref().is_some_and(|s| s.layout == rules::ProfileLayout::Electron) {
+                paths.retain(|(p, _)| !browser_roots.iter().any(|b| p.starts_with(b)));
             }
+            (r, paths, stores)
+        })
+        .collect();
+    let rank = |i: usize| (matched[i].0.wildcard_segments(), std::cmp::Reverse(i));
+    let claimed = |i: usize, p: &Path| {
+        matched.iter().enumerate().any(|(j, (other, paths, stores))| {
+            j != i && (stores.iter().any(|s| s == p) || (paths.iter().any(|(q, _)| q == p) && (!eligible(other) || rank(j) < rank(i))))
+        })
+    };
+    // What other rules match inside `p`, and the entries of `p` holding another rule's profiles
+    let nested = |i: usize, p: &Path| -> Vec<PathBuf> {
+        let mut out: Vec<PathBuf> = Vec::new();
+        for (_, (_, paths, stores)) in matched.iter().enumerate().filter(|(j, _)| *j != i) {
+            out.extend(paths.iter().map(|(q, _)| q).filter(|q| q.as_path() != p && q.starts_with(p)).cloned());
+            out.extend(stores.iter().filter_map(|s| s.strip_prefix(p).ok()?.components().next()).map(|c| p.join(c)));
         }
-        if let Ok(appdata) = std::env::var("APPDATA") {
-            if cats.teams_cache {
-                let teams_root = Path::new(&appdata).join("Microsoft/Teams");
-                v.push(teams_root.join("Cache"));
-                v.push(teams_root.join("GPUCache"));
-                v.push(teams_root.join("Service Worker/CacheStorage"));
-                v.push(teams_root.join("IndexedDB"));
-                v.push(teams_root.join("Local Storage"));
-            }
+        out.sort();
+        out.dedup();
+        out
+    };
+    let mut dirs: Vec<Target> = Vec::new();
+    let mut files: Vec<Target> = Vec::new();
+    for (i, (rule, paths, _)) in matched.iter().enumerate() {
+        if !eligible(rule) { continue; }
+        let out = match rule.kind { RuleKind::Dir => &mut dirs, RuleKind::File => &mut files };
+        for (path, instance) in paths {
+            if claimed(i, path) { continue; }
+            let mut keep = nested(i, path);
+            keep.extend(rule.keep.as_deref().map(|k| rules::expand_pattern(path, k)).unwrap_or_default());
+            out.push(Target { path: path.clone(), category: rule.category.clone(), min_age_hours: rule.min_age_hours, instance: instance.clone(), keep, keep_root: false });
         }
     }
+    (dirs, files)
+}
 
-    // System-level only if allowed explicitly
-    if allow_system {
-        if let Ok(windir) = std::env::var("WINDIR") {
-            if cats.windows_temp { v.push(Path::new(&windir).join("Temp")); }
-            if cats.prefetch { v.push(Path::new(&windir).join("Prefetch")); }
-            if cats.windows_update { v.push(Path::new(&windir).join("SoftwareDistribution/Download")); }
-            if cats.crash_dumps { v.push(Path::new(&windir).join("Minidump")); }
-            if cats.crash_dumps { v.push(Path::new(&windir).join("LiveKernelReports")); }
-            if cats.aspnet_temp {
-                v.push(Path::new(&windir).join("Microsoft.NET/Framework/v4.0.30319/Temporary ASP.NET Files"));
-                v.push(Path::new(&windir).join("Microsoft.NET/Framework64/v4.0.30319/Temporary ASP.NET Files"));
-            }
-        }
-        if let Ok(systemroot) = std::env::var("SystemRoot") {
-            if cats.windows_temp { v.push(Path::new(&systemroot).join("Temp")); }
-            if cats.windows_update { v.push(Path::new(&systemroot).join("SoftwareDistribution/Download")); }
+/// Targets of a run: the enabled catalog rules and custom targets, or the build output found by a sweep.
+/// Targets of a trim policy without `keep_mb` are left out; outer targets still clean around them.
+/// Nothing in a quarantine folder on another volume is ever a target.
+fn run_targets(cfg: &Config, catalog: &RuleCatalog, cats: &Categories, folders: &dyn KnownFolders, overrides: &RunOverrides) -> (Vec<Target>, Vec<Target>) {
+    let (mut dirs, mut files) = match &overrides.sweep {
+        Some(sweep) => {
+            let dirs = artifacts::discover(sweep).into_iter()
+                .map(|(path, _, category)| Target { path, category: category.to_string(), min_age_hours: None, instance: None, keep: Vec::new(), keep_root: false })
+                .collect();
+            (dirs, Vec::new())
         }
-        if let Ok(programdata) = std::env::var("ProgramData") {
-            if cats.delivery_optimization { v.push(Path::new(&programdata).join("Microsoft/Windows/DeliveryOptimization/Cache")); }
-            if cats.error_reports {
-                v.push(Path::new(&programdata).join("Microsoft/Windows/WER/ReportQueue"));
-                v.push(Path::new(&programdata).join("Microsoft/Windows/WER/ReportArchive"));
-                v.push(Path::new(&programdata).join("Microsoft/Windows/WER/Temp"));
-            }
-            if cats.defender_cache {
-                v.push(Path::new(&programdata).join("Microsoft/Windows Defender/Scans/History"));
-            }
+        None => {
+            let (mut dirs, mut files) = candidate_targets(catalog, cats, folders, overrides.allow_system);
+            let custom = RuleCatalog { rules: cfg.custom_rules() };
+            let (custom_dirs, custom_files) = candidate_targets(&custom, cats, folders, overrides.allow_system);
+            merge_custom_targets(&mut dirs, &mut files, custom_dirs, custom_files);
+            (dirs, files)
         }
+    };
+    dirs.retain(|t| !cfg.policy_for(&t.category).incomplete() && !quarantine::in_volume_dir(&t.path));
+    files.retain(|t| !cfg.policy_for(&t.category).incomplete() && !quarantine::in_volume_dir(&t.path));
+    (dirs, files)
+}
+
+/// Adds the custom targets to the catalog's. Custom folders are emptied, not removed. Where the two nest, the outer
+/// target is cleaned around the inner one, which keeps its own mode; at the same path the custom target wins.
+fn merge_custom_targets(dirs: &mut Vec<Target>, files: &mut Vec<Target>, mut custom_dirs: Vec<Target>, custom_files: Vec<Target>) {
+    let custom: Vec<PathBuf> = custom_dirs.iter().chain(&custom_files).map(|t| t.path.clone()).collect();
+    dirs.retain(|t| !custom.contains(&t.path));
+    files.retain(|t| !custom.contains(&t.path));
+    let inner = |outer: &Target, others: &[Target]| -> Vec<PathBuf> {
+        others.iter().map(|t| t.path.clone()).filter(|p| p != &outer.path && p.starts_with(&outer.path)).collect()
+    };
+    for t in custom_dirs.iter_mut() {
+        t.keep.extend([inner(t, dirs), inner(t, files)].concat());
+        t.keep_root = true;
+    }
+    for t in dirs.iter_mut() {
+        t.keep.extend(custom.iter().filter(|p| *p != &t.path && p.starts_with(&t.path)).cloned());
     }
+    dirs.extend(custom_dirs);
+    files.extend(custom_files);
+}
 
-    // Modern Apps (UWP) LocalCache/TempState
-    if cats.modern_apps_cache {
-        if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
-            let packages = Path::new(&localappdata).join("Packages");
-            if packages.is_dir() {
-                if let Ok(rd) = fs::read_dir(&packages) {
-                    for e in rd.flatten() {
-                        let p = e.path();
-                        if p.is_dir() {
-                            v.push(p.join("LocalCache"));
-                            v.push(p.join("TempState"));
-                        }
-                    }
-                }
-            }
+/// Where a run may operate: the allowed prefixes and the profiles of `dirs` and `files` found outside them
+/// (Gecko profiles with a custom `Path=`), or the workspace roots of a sweep.
+fn run_bases(folders: &dyn KnownFolders, overrides: &RunOverrides, dirs: &[Target], files: &[Target]) -> Vec<PathBuf> {
+    match &overrides.sweep {
+        Some(sweep) => sweep.roots.iter().filter(|r| r.is_dir()).cloned().collect(),
+        None => {
+            let mut bases = allowed_prefixes(folders, overrides.allow_system);
+            let profiles: Vec<PathBuf> = dirs.iter().chain(files)
+                .filter_map(|t| t.instance.as_ref().map(|i| i.dir.clone()))
+                // Never a root, nor a folder holding a protected one
+                .filter(|d| d.is_dir() && d.parent().is_some() && !folders.sensitive_dirs().iter().any(|s| is_under(s, d)))
+                .filter(|d| !bases.iter().any(|b| is_under(d, b)))
+                .collect();
+            bases.extend(profiles);
+            bases.sort();
+            bases.dedup();
+            bases
         }
     }
+}
 
-    // Keep only existing directories
-    v.retain(|p| p.is_dir());
-    v
-}
-
-fn candidate_files(cats: &Categories, allow_system: bool) -> Vec<PathBuf> {
-    let mut v: Vec<PathBuf> = Vec::new();
-    if cats.thumbnails {
-        if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
-            let explorer = Path::new(&localappdata).join("Microsoft/Windows/Explorer");
-            if explorer.is_dir() {
-                if let Ok(rd) = fs::read_dir(&explorer) {
-                    for e in rd.flatten() {
-                        let p = e.path();
-                        if let Some(name) = p.file_name().and_then(|s| s.to_str()) {
-                            let lower = name.to_ascii_lowercase();
-                            // Windows 10/11 store thumbnail and icon caches primarily as DB files under this folder.
-                            // Include all common variants, not just *.db, to match Windows Settings cleanup more closely.
-                            if lower.starts_with("thumbcache") || lower.starts_with("iconcache") {
-                                v.push(p.clone());
-                            }
-                        }
-                    }
-                }
-            }
-        }
+/// Worker threads for a run: up to 8 by default, or `max_parallelism` clamped to the available cores.
+fn worker_count(overrides: &RunOverrides) -> usize {
+    let avail = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    match overrides.max_parallelism {
+        Some(n) => n.clamp(1, avail),
+        None => avail.min(8),
     }
-    // Large system dump file
-    if allow_system && cats.crash_dumps {
-        if let Ok(windir) = std::env::var("WINDIR") {
-            let memdmp = Path::new(&windir).join("MEMORY.DMP");
-            if memdmp.is_file() {
-                v.push(memdmp);
-            }
+}
+
+fn run_job<'a>(job: Job<'a>, targets: &'a [Target], ctx: &'a RunCtx<'a>, w: &Worker<'_, Job<'a>>) {
+    match job {
+        Job::Target(i) => {
+            if ctx.cancelled() { return; }
+            let target = &targets[i];
+            ctx.emit(CleanEvent::TargetStarted { path: &target.path, category: &target.category });
+            clean_dir_target(target, ctx, w);
         }
+        Job::Dir(node) => walker::process_dir(node, w),
     }
-    v
 }
 
-fn fast_clean_dir(dir: &Path, cfg: &Config, stats: &Stats) {
-    let dry_run = cfg.dry_run;
-    let verbose = cfg.verbose && !cfg.quiet;
-    if !dir.is_dir() { return; }
+fn target_finished(ctx: &RunCtx, target: &Target) {
+    ctx.emit(CleanEvent::TargetFinished { path: &target.path, category: &target.category });
+}
+
+/// Cleans one directory target. Tree walks are queued on the pool; whichever worker completes
+/// the walk books the result and reports the target as finished.
+fn clean_dir_target<'a>(target: &'a Target, ctx: &'a RunCtx<'a>, w: &Worker<'_, Job<'a>>) {
+    let (cfg, stats) = (ctx.cfg, ctx.stats);
+    let dir = target.path.as_path();
+    let filter = EntryFilter::for_target(cfg, target);
+    if !dir.is_dir() { return target_finished(ctx, target); }
     // Do not operate on filesystem roots (e.g., C:\)
-    if dir.parent().is_none() { return; }
+    if dir.parent().is_none() {
+        ctx.skipped(dir, FailureReason::FilesystemRoot);
+        return target_finished(ctx, target);
+    }
     // Extra safety: never operate on highly sensitive top-level system directories
-    if is_sensitive_dir(dir) { return; }
+    if is_sensitive_dir(ctx.folders, dir) {
+        ctx.skipped(dir, FailureReason::SensitiveDir);
+        return target_finished(ctx, target);
+    }
     // Avoid traversing reparse points (junctions/symlinks) to reduce risk
     if is_reparse_point(dir) {
         // Best-effort: remove the link itself
-        if dry_run {
-            if verbose { println!("[dry-run] Would remove reparse link: {}", dir.display()); }
-            stats.add_links(1);
-            return;
-        }
-        if fs::remove_dir(dir).or_else(|_| fs::remove_file(dir)).is_ok() {
-            stats.add_links(1);
+        let res = if cfg.dry_run { Ok(()) } else { ctx.dispose_link(dir) };
+        match res {
+            Ok(()) => {
+                ctx.removed(dir, EntryKind::Link, 0, 0, 0);
+                stats.add_links(1);
+            }
+            Err(e) => ctx.failed(dir, &e),
         }
+        return target_finished(ctx, target);
+    }
+    // Trimmed caches keep their most recently used files; dry-run reports what would be evicted
+    if let Some(keep) = cfg.policy_for(&target.category).trim_keep() {
+        return trim::trim_target(target, ctx, filter, keep, w);
+    }
+    // A retention filter never removes the target itself, nor is a custom target or a base folder ever removed
+    let keep_root = target.keep_root || filter.is_active() || is_base_folder(ctx.folders, dir);
+    if cfg.dry_run {
+        TreeWalk::new(filter, Some(ctx), false).start(dir, w, move |mut count, emptied, _| {
+            if emptied && !keep_root { count.dirs += 1; } // include the root dir unless something in it stays
+            ctx.removed(dir, EntryKind::Dir, count.files, count.dirs, count.bytes);
+            stats.add_count(&count);
+            stats.add_cleaned_dir(dir);
+            target_finished(ctx, target);
+        });
         return;
     }
-    // Try to remove entirely, otherwise fall back to shallow file cleanup
-    if dry_run {
-        let (bytes, files, dirs) = compute_dir_stats(dir);
-        if verbose { println!("[dry-run] Would remove dir (all): {} ({} files, {} dirs, {} bytes)", dir.display(), files, dirs, bytes); }
-        stats.add_bytes(bytes);
-        stats.add_files(files);
-        stats.add_dirs(dirs + 1); // include the root dir
-        stats.add_cleaned_dir(dir);
+    if !keep_root && ctx.quarantine.is_some() {
+        // The quarantine manifest wants the size up front: count in parallel, then move the target with one rename
+        // The count walk also tells whether anything in the tree has to stay; then only entry by entry will do
+        TreeWalk::new(filter.clone(), Some(ctx), false).start(dir, w, move |count, emptied, w| {
+            if ctx.cancelled() { return target_finished(ctx, target); }
+            set_writable(dir);
+            if emptied && ctx.dispose(dir, true, count.bytes).is_ok() {
+                let count = TreeCount { dirs: count.dirs + 1, ..count };
+                ctx.removed(dir, EntryKind::Dir, count.files, count.dirs, count.bytes);
+                stats.add_count(&count);
+                stats.add_cleaned_dir(dir);
+                return target_finished(ctx, target);
+            }
+            // E.g. a locked file inside: quarantine entry by entry instead
+            delete_walk(target, ctx, filter, keep_root, w);
+        });
         return;
     }
-    // Fast path: in fast mode, attempt removal without pre-counting
-    if !cfg.exact_stats {
-        // Versuche das Verzeichnis selbst schreibbar zu machen, damit remove_dir_all nicht an Readonly-Attributen scheitert
-        set_writable(dir);
-        if fs::remove_dir_all(dir).is_ok() {
-            // We do not know exact bytes/files removed in fast mode
-            stats.add_dirs(1); // count the root dir removed
-            stats.add_cleaned_dir(dir);
-            return;
-        }
-    } else {
-        // Exact stats mode: pre-count to report accurate freed bytes
-        let (bytes_all, files_all, mut dirs_all) = compute_dir_stats(dir);
-        dirs_all += 1; // include the root dir
-        // Auch hier vorab schreibbar setzen
-        set_writable(dir);
-        if fs::remove_dir_all(dir).is_ok() {
-            stats.add_bytes(bytes_all);
-            stats.add_files(files_all);
-            stats.add_dirs(dirs_all);
-            stats.add_cleaned_dir(dir);
-            return;
-        }
-    }
-    // Shallow cleanup on failure
-    if let Ok(rd) = fs::read_dir(dir) {
-        for e in rd.flatten() {
-            let p = e.path();
-            if is_reparse_point(&p) {
-                // Remove the link itself (dir or file-like reparse)
-                if dry_run {
-                    if verbose { println!("[dry-run] Would remove reparse link: {}", p.display()); }
-                    stats.add_links(1);
-                    continue;
+    // Fast path: in fast mode, hand the whole tree to remove_dir_all without counting.
+    // A retention filter goes straight to the per-entry walk below; so does a tree the parallel probe finds kept entries in.
+    if !keep_root && !cfg.exact_stats {
+        TreeWalk::new(filter.clone(), Some(ctx), false).probe().start(dir, w, move |_, emptied, w| {
+            if ctx.cancelled() { return target_finished(ctx, target); }
+            if emptied {
+                // Versuche das Verzeichnis selbst schreibbar zu machen, damit remove_dir_all nicht an Readonly-Attributen scheitert
+                set_writable(dir);
+                if ctx.dispose(dir, true, 0).is_ok() {
+                    // We do not know exact bytes/files removed in fast mode
+                    ctx.removed(dir, EntryKind::Dir, 0, 1, 0);
+                    stats.add_dirs(1); // count the root dir removed
+                    stats.add_cleaned_dir(dir);
+                    return target_finished(ctx, target);
                 }
-                if fs::remove_dir(&p).or_else(|_| fs::remove_file(&p)).is_ok() {
-                    stats.add_links(1);
-                }
-                continue;
             }
-            if p.is_dir() {
-                set_writable(&p);
-                let (bytes, files, mut dirs) = compute_dir_stats(&p);
-                if dry_run {
-                    if verbose { println!("[dry-run] Would remove dir: {} ({} files, {} dirs, {} bytes)", p.display(), files, dirs, bytes); }
-                    stats.add_bytes(bytes);
-                    stats.add_files(files);
-                    stats.add_dirs(dirs + 1);
-                    stats.add_cleaned_dir(&p);
-                } else {
-                    if fs::remove_dir_all(&p).is_ok() {
-                        stats.add_bytes(bytes);
-                        stats.add_files(files);
-                        dirs += 1; // include the dir itself
-                        stats.add_dirs(dirs);
-                        stats.add_cleaned_dir(&p);
-                    }
-                }
+            delete_walk(target, ctx, filter, keep_root, w);
+        });
+        return;
+    }
+    delete_walk(target, ctx, filter, keep_root, w);
+}
+
+/// Single pass (exact mode, fallback after a failed fast path, or retention filter):
+/// remove entry by entry in parallel and count only what is really gone.
+fn delete_walk<'a>(target: &'a Target, ctx: &'a RunCtx<'a>, filter: EntryFilter, keep_root: bool, w: &Worker<'_, Job<'a>>) {
+    let dir = target.path.as_path();
+    set_writable(dir);
+    TreeWalk::new(filter, Some(ctx), true).with_reboot_fallback().start(dir, w, move |count, emptied, _| {
+        finish_target(dir, ctx, count, emptied && !keep_root);
+        target_finished(ctx, target);
+    });
+}
+
+/// Books the per-entry results of a target and removes the emptied target directory.
+fn finish_target(dir: &Path, ctx: &RunCtx, mut count: TreeCount, remove_root: bool) {
+    if remove_root {
+        match fs::remove_dir(dir) {
+            Ok(()) => {
+                ctx.removed(dir, EntryKind::Dir, 0, 1, 0);
+                count.dirs += 1;
+            }
+            Err(e) => ctx.failed(dir, &e),
+        }
+    }
+    if count.files + count.dirs + count.links > 0 {
+        ctx.stats.add_count(&count);
+        ctx.stats.add_cleaned_dir(dir);
+    }
+}
+
+/// Removes a single file target (e.g. Explorer's thumbnail caches).
+fn clean_file_target(f: &Path, ctx: &RunCtx) {
+    let stats = ctx.stats;
+    let md = fs::metadata(f).ok();
+    let (size, allocated) = md.as_ref().map(|m| (m.len(), allocated_size(f, m))).unwrap_or((0, 0));
+    let shared = md.as_ref().and_then(|m| hardlink_id(f, m));
+    let book = || {
+        match shared {
+            Some((id, nlink)) => stats.hardlinks.record(id, nlink, size, allocated),
+            None => stats.add_bytes(size, allocated),
+        }
+        stats.add_files(1);
+        if let Some(parent) = f.parent() { stats.add_cleaned_dir(parent); }
+    };
+    if ctx.cfg.dry_run {
+        ctx.removed(f, EntryKind::File, 1, 0, size);
+        return book();
+    }
+    set_writable(f);
+    match ctx.dispose(f, false, size) {
+        Ok(()) => {
+            ctx.removed(f, EntryKind::File, 1, 0, size);
+            book();
+        }
+        Err(e) => {
+            // As a fallback on Windows, schedule deletion on next reboot (locked files like Explorer caches)
+            if ctx.quarantine.is_none() && schedule_delete_on_reboot(f) {
+                ctx.emit(CleanEvent::EntryScheduled { path: f, bytes: size });
+                // Count bytes and files as they will be freed on reboot
+                book();
             } else {
-                set_writable(&p);
-                let size = fs::metadata(&p).map(|m| m.len()).unwrap_or(0);
-                if dry_run {
-                    if verbose { println!("[dry-run] Would remove file: {} ({} bytes)", p.display(), size); }
-                    stats.add_bytes(size);
-                    stats.add_files(1);
-                } else {
-                    if fs::remove_file(&p).is_ok() {
-                        stats.add_bytes(size);
-                        stats.add_files(1);
-                        if verbose { println!("Removed file: {} ({} bytes)", p.display(), size); }
-                    } else {
-                        // On Windows, schedule deletion on next reboot for locked files
-                        #[cfg(windows)]
-                        {
-                            if schedule_delete_on_reboot(&p) {
-                                if verbose { println!("Scheduled for deletion on reboot: {} ({} bytes)", p.display(), size); }
-                                stats.add_bytes(size);
-                                stats.add_files(1);
-                            }
-                        }
-                    }
-                }
+                ctx.failed(f, &e);
             }
         }
     }
 }
 
-#[cfg(windows)]
-fn is_reparse_point(p: &Path) -> bool {
-    if let Ok(md) = fs::symlink_metadata(p) {
-        // FILE_ATTRIBUTE_REPARSE_POINT = 0x0400
-        (md.file_attributes() & 0x0400) != 0
-    } else {
-        false
+/// Decides which entries below a target may be removed.
+#[derive(Debug, Clone, Default)]
+struct EntryFilter {
+    /// Entries touched after this instant are kept
+    cutoff: Option<SystemTime>,
+    /// Entries the config excludes, kept with everything below them
+    exclude: keep::Exclusions,
+}
+
+impl EntryFilter {
+    fn for_target(cfg: &Config, t: &Target) -> Self {
+        let cutoff = cfg.min_age_hours(&t.category, t.min_age_hours)
+            .filter(|h| *h > 0)
+            .and_then(|h| SystemTime::now().checked_sub(Duration::from_secs(h.saturating_mul(3600))));
+        Self { cutoff, exclude: keep::Exclusions::new(&cfg.exclude).with_paths(&t.keep) }
+    }
+
+    fn is_active(&self) -> bool { self.cutoff.is_some() }
+
+    /// Files (and links) qualify when both their mtime and atime are older than the cutoff.
+    fn allows(&self, md: &fs::Metadata) -> bool {
+        let Some(cutoff) = self.cutoff else { return true };
+        let touched = [md.modified().ok(), md.accessed().ok()].into_iter().flatten().max();
+        matches!(touched, Some(t) if t <= cutoff)
+    }
+
+    /// Directories only look at mtime: listing a directory (including our own scans) bumps its atime.
+    fn allows_dir(&self, md: &fs::Metadata) -> bool {
+        let Some(cutoff) = self.cutoff else { return true };
+        matches!(md.modified(), Ok(t) if t <= cutoff)
     }
 }
 
+/// What a walk removed (or would remove).
+#[derive(Debug, Clone, Copy, Default)]
+struct TreeCount {
+    bytes: u64,
+    /// Allocated size on disk of the counted files
+    allocated: u64,
+    files: u64,
+    dirs: u64,
+    links: u64,
+    /// Hard-linked data whose other links survive, so removing these paths frees nothing
+    unfreed: u64,
+}
+
+#[cfg(windows)]
+fn is_reparse_metadata(md: &fs::Metadata) -> bool {
+    // FILE_ATTRIBUTE_REPARSE_POINT = 0x0400
+    (md.file_attributes() & 0x0400) != 0
+}
+
+/// Symlinks are the only kind of link to a directory elsewhere.
 #[cfg(not(windows))]
-fn is_reparse_point(_p: &Path) -> bool { false }
+fn is_reparse_metadata(md: &fs::Metadata) -> bool { md.file_type().is_symlink() }
+
+/// Whether `p` itself (not what it points to) is a link: junction, symlink or other reparse point.
+fn is_reparse_point(p: &Path) -> bool {
+    fs::symlink_metadata(p).map(|md| is_reparse_metadata(&md)).unwrap_or(false)
+}
+
+/// Sockets, FIFOs and device nodes: left alone, they belong to running programs (e.g. `/tmp/.X11-unix`).
+#[cfg(unix)]
+fn is_special_file(md: &fs::Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    let ft = md.file_type();
+    ft.is_socket() || ft.is_fifo() || ft.is_block_device() || ft.is_char_device()
+}
+
+#[cfg(not(unix))]
+fn is_special_file(_md: &fs::Metadata) -> bool { false }
+
+/// Who we are in `dir` if it is sticky (`/tmp`), where only an entry's owner may remove it; `None` if it is not
+/// sticky or we run as root.
+#[cfg(unix)]
+fn sticky_owner(dir: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let sticky = fs::metadata(dir).is_ok_and(|m| m.permissions().mode() & 0o1000 != 0);
+    let uid = unsafe { libc::geteuid() };
+    (sticky && uid != 0).then_some(uid)
+}
+
+#[cfg(not(unix))]
+fn sticky_owner(_dir: &Path) -> Option<u32> { None }
+
+#[cfg(unix)]
+fn owned_by(md: &fs::Metadata, uid: u32) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    md.uid() == uid
+}
+
+#[cfg(not(unix))]
+fn owned_by(_md: &fs::Metadata, _uid: u32) -> bool { true }
+
+/// Whether `p` is one of the well-known base folders itself (e.g. `/tmp`, `%TEMP%`): those are emptied, never removed.
+fn is_base_folder(folders: &dyn KnownFolders, p: &Path) -> bool {
+    let Some(full) = canonicalize_ok(p) else { return false };
+    KnownFolder::ALL.iter().filter_map(|f| folders.folder(*f)).any(|b| canonicalize_ok(&b).is_some_and(|c| c == full))
+}
 
 fn set_writable(path: &Path) {
     if let Ok(metadata) = fs::metadata(path) {
         let mut perms = metadata.permissions();
         if perms.readonly() {
             // Ignore errors: best-effort
+            #[cfg(windows)]
             perms.set_readonly(false);
+            // Only grant the owner write access; `set_readonly(false)` would make it world-writable
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                perms.set_mode(perms.mode() | 0o200);
+            }
             let _ = fs::set_permissions(path, perms);
         }
     }
 }
 
-fn is_sensitive_dir(p: &Path) -> bool {
-    // Only meaningful on Windows, but safe elsewhere
+fn is_sensitive_dir(folders: &dyn KnownFolders, p: &Path) -> bool {
     let full = match p.canonicalize() { Ok(x) => x, Err(_) => return false };
-    let full_str = full.to_string_lossy().to_ascii_lowercase();
-
-    let mut sens: Vec<String> = Vec::new();
-    if let Ok(windir) = std::env::var("WINDIR") {
-        sens.push(Path::new(&windir).to_string_lossy().to_ascii_lowercase());
-    }
-    if let Ok(systemroot) = std::env::var("SystemRoot") {
-        sens.push(Path::new(&systemroot).to_string_lossy().to_ascii_lowercase());
-    }
-    if let Ok(pf) = std::env::var("ProgramFiles") {
-        sens.push(Path::new(&pf).to_string_lossy().to_ascii_lowercase());
-    }
-    if let Ok(pfx86) = std::env::var("ProgramFiles(x86)") {
-        sens.push(Path::new(&pfx86).to_string_lossy().to_ascii_lowercase());
-    }
-    if let Ok(pd) = std::env::var("ProgramData") {
-        sens.push(Path::new(&pd).to_string_lossy().to_ascii_lowercase());
-    }
-    if let Ok(sysdrive) = std::env::var("SystemDrive") {
-        // Users root (e.g., C:\\Users)
-        let users_root_str = format!("{}\\Users", sysdrive);
-        let users_root = Path::new(&users_root_str);
-        sens.push(users_root.to_string_lossy().to_ascii_lowercase());
-    }
-
-    sens.iter().any(|s| full_str == *s)
-}
-
-fn push_chromium_caches(targets: &mut Vec<PathBuf>, product_root: PathBuf) {
-    // product_root like %LOCALAPPDATA%/Google/Chrome
-    let user_data = product_root.join("User Data");
-    if !user_data.is_dir() { return; }
-    if let Ok(rd) = fs::read_dir(&user_data) {
-        for e in rd.flatten() {
-            let profile = e.path();
-            if !profile.is_dir() { continue; }
-            // Common cache subfolders in Chromium-based browsers
-            targets.push(profile.join("Cache"));
-            targets.push(profile.join("Code Cache"));
-            targets.push(profile.join("GPUCache"));
-            targets.push(profile.join("ShaderCache"));
-            targets.push(profile.join("DawnCache"));
-            targets.push(profile.join("GrShaderCache"));
-            targets.push(profile.join("Media Cache"));
-            targets.push(profile.join("Service Worker/CacheStorage"));
-            targets.push(profile.join("Application Cache"));
-            targets.push(profile.join("Network/Cache"));
-        }
-    }
-    // Product-wide shader cache (not per-profile)
-    targets.push(user_data.join("ShaderCache/GPUCache"));
-}
-
-fn compute_dir_stats(root: &Path) -> (u64, u64, u64) {
-    // bytes, files, dirs (excluding root)
-    let mut bytes: u64 = 0;
-    let mut files: u64 = 0;
-    let mut dirs: u64 = 0;
-    if !root.is_dir() { return (0, 0, 0); }
-    let mut stack: Vec<PathBuf> = vec![root.to_path_buf()];
-    while let Some(d) = stack.pop() {
-        if let Ok(rd) = fs::read_dir(&d) {
-            for e in rd.flatten() {
-                let p = e.path();
-                if is_reparse_point(&p) { continue; }
-                if p.is_dir() {
-                    dirs += 1;
-                    stack.push(p);
-                } else {
-                    files += 1;
-                    bytes = bytes.saturating_add(fs::metadata(&p).map(|m| m.len()).unwrap_or(0));
-                }
-            }
+    // Compare canonical forms on both sides (drive letter case, `\\?\` prefix, 8.3 names)
+    folders.sensitive_dirs().iter().any(|s| s.canonicalize().map(|c| c == full).unwrap_or(false))
+}
+
+fn compute_dir_stats(root: &Path, filter: &EntryFilter, threads: usize) -> TreeCount {
+    // What a cleanup under `filter` would remove below `root` (excluding root itself)
+    if !root.is_dir() { return TreeCount::default(); }
+    let (count, _) = walker::walk_tree(root, TreeWalk::new(filter.clone(), None, false), threads);
+    count
+}
+
+/// Space a file occupies on disk: `st_blocks` on Unix.
+#[cfg(unix)]
+fn allocated_size(_p: &Path, md: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    md.blocks() * 512
+}
+
+/// Space a file occupies on disk. Compressed and sparse files ask NTFS for their real size;
+/// everything else is rounded up to the default 4 KiB cluster (tiny files resident in the MFT are overcounted).
+#[cfg(windows)]
+fn allocated_size(p: &Path, md: &fs::Metadata) -> u64 {
+    use windows_sys::Win32::Storage::FileSystem::{GetCompressedFileSizeW, INVALID_FILE_SIZE};
+    // FILE_ATTRIBUTE_SPARSE_FILE = 0x0200, FILE_ATTRIBUTE_COMPRESSED = 0x0800
+    if md.file_attributes() & (0x0200 | 0x0800) != 0 {
+        let wide: Vec<u16> = p.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+        let mut high: u32 = 0;
+        let low = unsafe { GetCompressedFileSizeW(wide.as_ptr(), &mut high) };
+        // INVALID_FILE_SIZE is also a valid low word, GetLastError tells them apart; treat both as unknown
+        if low != INVALID_FILE_SIZE {
+            return ((high as u64) << 32) | low as u64;
         }
     }
-    (bytes, files, dirs)
+    (md.len() + 4095) & !4095
 }
 
+#[cfg(not(any(unix, windows)))]
+fn allocated_size(_p: &Path, md: &fs::Metadata) -> u64 { md.len() }
+
 pub fn format_bytes(bytes: u64) -> String {
     const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
     let mut size = bytes as f64;
@@ -816,7 +1353,6 @@ pub fn format_bytes(bytes: u64) -> String {
     if unit == 0 { format!("{} {}", bytes, UNITS[unit]) } else { format!("{:.2} {}", size, UNITS[unit]) }
 }
 
-// helpers for serde defaults
 pub fn env_truthy(name: &str) -> bool {
     match std::env::var(name) {
         Ok(v) => {
@@ -826,8 +1362,6 @@ pub fn env_truthy(name: &str) -> bool {
         Err(_) => false,
     }
 }
-fn true_bool() -> bool { true }
-fn false_bool() -> bool { false }
 
 // ---------------- Windows-specific helpers ----------------
 #[cfg(windows)]
@@ -847,16 +1381,18 @@ pub fn is_elevated() -> bool {
         is_member != 0
     }
 }
-#[cfg(not(windows))]
+#[cfg(unix)]
+pub fn is_elevated() -> bool { unsafe { libc::geteuid() == 0 } }
+#[cfg(not(any(unix, windows)))]
 pub fn is_elevated() -> bool { false }
 #[cfg(windows)]
-fn stop_explorer(cfg: &Config) -> bool {
+fn stop_explorer() -> bool {
     // Attempt a graceful stop of Explorer to release locks on caches
     // Use taskkill /IM explorer.exe /F
     let res = Command::new("taskkill").args(["/IM", "explorer.exe", "/F"]).status();
     match res {
         Ok(st) if st.success() => {
-            if cfg.verbose && !cfg.quiet { println!("Stopped Explorer.exe to unlock caches"); }
+            log::info!("Stopped Explorer.exe to unlock caches");
             // Give it a moment to terminate
             std::thread::sleep(Duration::from_millis(300));
             true
@@ -866,9 +1402,9 @@ fn stop_explorer(cfg: &Config) -> bool {
 }
 
 #[cfg(windows)]
-fn start_explorer(cfg: &Config) {
+fn start_explorer() {
     let _ = Command::new("explorer.exe").status();
-    if cfg.verbose && !cfg.quiet { println!("Restarted Explorer.exe"); }
+    log::info!("Restarted Explorer.exe");
 }
 
 #[cfg(windows)]
@@ -877,3 +1413,586 @@ fn schedule_delete_on_reboot(p: &Path) -> bool {
     let wide: Vec<u16> = p.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
     unsafe { MoveFileExW(wide.as_ptr(), std::ptr::null(), MOVEFILE_DELAY_UNTIL_REBOOT) != 0 }
 }
+
+#[cfg(not(windows))]
+fn schedule_delete_on_reboot(_p: &Path) -> bool { false }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let d = std::env::temp_dir().join(format!("zentify-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&d);
+        fs::create_dir_all(&d).unwrap();
+        d
+    }
+
+    /// A virtual machine in a scratch dir with `rules` installed as a user rule file. Rules without a `base`
+    /// resolve against an allowed user folder no broad built-in rule covers (`%LOCALAPPDATA%`, or `~/.cache` with
+    /// its catch-all rule replaced), `scope` defaults to user.
+    struct RuleFixture {
+        root: PathBuf,
+        /// The folder rules resolve against by default
+        base: PathBuf,
+        overrides: RunOverrides,
+    }
+
+    fn rule_fixture(name: &str, mut rules: serde_json::Value) -> RuleFixture {
+        let root = scratch_dir(name);
+        let folders = VirtualRoot::new(&root);
+        let base = if cfg!(windows) { KnownFolder::LocalAppData } else { KnownFolder::XdgCache };
+        for rule in rules.as_array_mut().into_iter().flatten().filter_map(|r| r.as_object_mut()) {
+            rule.entry("base").or_insert(serde_json::json!(base));
+            rule.entry("scope").or_insert(serde_json::json!("user"));
+        }
+        // The `~/.cache/*` catch-all would take the fixture's folders for itself
+        if !cfg!(windows) {
+            rules.as_array_mut().unwrap().push(serde_json::json!({ "id": "xdg_cache_dirs", "category": "xdg_cache", "base": base, "path": "none", "scope": "user" }));
+        }
+        let rules_dir = folders.config_dirs().pop().unwrap().join("rules");
+        fs::create_dir_all(&rules_dir).unwrap();
+        fs::write(rules_dir.join("test.json"), serde_json::json!({ "rules": rules }).to_string()).unwrap();
+        let base = folders.folder(base).unwrap();
+        RuleFixture { root, base, overrides: RunOverrides { folders: Some(Arc::new(folders)), ..Default::default() } }
+    }
+
+    impl RuleFixture {
+        /// Creates files of the given sizes below the base folder.
+        fn files(&self, files: &[(&str, usize)]) {
+            for (file, size) in files {
+                let p = self.base.join(file);
+                fs::create_dir_all(p.parent().unwrap()).unwrap();
+                fs::write(p, vec![0u8; *size]).unwrap();
+            }
+        }
+    }
+
+    fn dir_stats(root: &Path, filter: &EntryFilter, threads: usize) -> (u64, u64, u64) {
+        let c = compute_dir_stats(root, filter, threads);
+        (c.bytes, c.files, c.dirs)
+    }
+
+    #[test]
+    fn test_policy_for_falls_back_to_default() {
+        let mut cfg = Config::default();
+        cfg.policies.insert("default".into(), CategoryPolicy { min_age_hours: Some(24), ..Default::default() });
+        cfg.policies.insert("user_temp".into(), CategoryPolicy { min_age_hours: Some(48), ..Default::default() });
+        assert_eq!(cfg.policy_for("user_temp").min_age_hours, Some(48));
+        assert_eq!(cfg.policy_for("browser_cache").min_age_hours, Some(24));
+        assert_eq!(Config::default().policy_for("user_temp").min_age_hours, None);
+    }
+
+    #[test]
+    fn test_retention_filter_keeps_young_entries() {
+        let root = scratch_dir("retention");
+        fs::create_dir_all(root.join("sub/deeper")).unwrap();
+        fs::write(root.join("a.tmp"), b"12345").unwrap();
+        fs::write(root.join("sub/deeper/b.tmp"), b"123").unwrap();
+
+        // Everything was touched after the epoch, so nothing qualifies
+        let keep_all = EntryFilter { cutoff: Some(SystemTime::UNIX_EPOCH), ..EntryFilter::default() };
+        assert_eq!(dir_stats(&root, &keep_all, 4), (0, 0, 0));
+
+        // A cutoff in the future makes every entry old enough
+        let take_all = EntryFilter { cutoff: Some(SystemTime::now() + Duration::from_secs(3600)), ..EntryFilter::default() };
+        assert_eq!(dir_stats(&root, &take_all, 4), (8, 2, 2));
+        let cfg = Config::default();
+        let stats = Stats::default();
+        let ctx = RunCtx { cfg: &cfg, folders: &SystemFolders, stats: &stats, quarantine: None, observer: None, cancel: None };
+        let (count, emptied) = walker::walk_tree(&root, TreeWalk::new(take_all, Some(&ctx), true), 4);
+        assert!(emptied);
+        assert_eq!((count.bytes, count.files, count.dirs), (8, 2, 2));
+        assert!(root.is_dir());
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_observer_sees_removed_target() {
+        let root = scratch_dir("observer");
+        let target = root.join("Cache");
+        fs::create_dir_all(target.join("sub")).unwrap();
+        fs::write(target.join("sub/a.bin"), b"1234").unwrap();
+
+        let seen = Mutex::new(Vec::new());
+        let observer = |ev: &CleanEvent| {
+            if let CleanEvent::EntryRemoved { path, kind, files, dirs, bytes } = ev {
+                seen.lock().unwrap().push((path.to_path_buf(), *kind, (*files, *dirs, *bytes)));
+            }
+        };
+        let cfg = Config { exact_stats: true, ..Config::default() };
+        let stats = Stats::default();
+        let ctx = RunCtx { cfg: &cfg, folders: &SystemFolders, stats: &stats, quarantine: None, observer: Some(&observer), cancel: None };
+        let targets = vec![Target { path: target.clone(), category: "test".into(), min_age_hours: None, instance: None, keep: Vec::new(), keep_root: false }];
+        walker::run(4, vec![Job::Target(0)], |job, w| run_job(job, &targets, &ctx, w));
+        assert!(!target.exists());
+        // Exact mode removes entry by entry: children first, the target itself last
+        let seen = seen.into_inner().unwrap();
+        assert_eq!(seen.first(), Some(&(target.join("sub/a.bin"), EntryKind::File, (1, 0, 4))));
+        assert_eq!(seen.last(), Some(&(target.clone(), EntryKind::Dir, (0, 1, 0))));
+        assert_eq!(stats.snapshot(), (1, 2, 0, 4));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_parallel_walk_counts_wide_tree() {
+        let root = scratch_dir("parallel");
+        for i in 0..20 {
+            let d = root.join(format!("d{}/x/y", i));
+            fs::create_dir_all(&d).unwrap();
+            fs::write(d.join("f.bin"), vec![0u8; i]).unwrap();
+        }
+        let expected = ((0..20).sum::<usize>() as u64, 20, 60);
+        assert_eq!(dir_stats(&root, &EntryFilter::default(), 1), expected);
+        assert_eq!(dir_stats(&root, &EntryFilter::default(), 8), expected);
+
+        let cfg = Config::default();
+        let stats = Stats::default();
+        let ctx = RunCtx { cfg: &cfg, folders: &SystemFolders, stats: &stats, quarantine: None, observer: None, cancel: None };
+        let (count, emptied) = walker::walk_tree(&root, TreeWalk::new(EntryFilter::default(), Some(&ctx), true), 8);
+        assert!(emptied);
+        assert_eq!((count.bytes, count.files, count.dirs), expected);
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_cancelled_walk_keeps_entries() {
+        let root = scratch_dir("cancel");
+        fs::write(root.join("a.tmp"), b"1").unwrap();
+        fs::write(root.join("b.tmp"), b"2").unwrap();
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let cfg = Config::default();
+        let stats = Stats::default();
+        let ctx = RunCtx { cfg: &cfg, folders: &SystemFolders, stats: &stats, quarantine: None, observer: None, cancel: Some(&cancel) };
+        let take_all = EntryFilter { cutoff: Some(SystemTime::now() + Duration::from_secs(3600)), ..EntryFilter::default() };
+        let (count, emptied) = walker::walk_tree(&root, TreeWalk::new(take_all, Some(&ctx), true), 2);
+        assert!(!emptied);
+        assert_eq!(count.files, 0);
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 2);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_virtual_root_rehearsal() {
+        let root = scratch_dir("virtual");
+        let folders = VirtualRoot::new(&root);
+        // %TEMP% on Windows; elsewhere /tmp only takes old entries, so use a ~/.cache subtree
+        let (base, target) = if cfg!(windows) {
+            let temp = folders.folder(KnownFolder::Temp).unwrap();
+            (temp.clone(), temp)
+        } else {
+            let cache = folders.folder(KnownFolder::XdgCache).unwrap();
+            (cache.clone(), cache.join("setup"))
+        };
+        fs::create_dir_all(base.join("setup")).unwrap();
+        fs::write(base.join("setup/a.tmp"), b"123").unwrap();
+        // Present in the fake tree, but the rules are system scope
+        fs::create_dir_all(root.join("Windows/Temp")).unwrap();
+        fs::write(root.join("Windows/Temp/b.tmp"), b"1").unwrap();
+        fs::create_dir_all(root.join("var/crash")).unwrap();
+        fs::write(root.join("var/crash/b.crash"), b"1").unwrap();
+
+        let cfg = Config { dry_run: true, ..Config::default() };
+        let overrides = RunOverrides { folders: Some(Arc::new(folders)), ..Default::default() };
+        let preview = preview_targets(&cfg, &overrides);
+        assert_eq!(preview.target_dirs, vec![target.to_string_lossy().to_string()]);
+        let summary = run_clean(&cfg, &overrides);
+        assert_eq!((summary.files_deleted, summary.bytes_freed), (1, 3));
+        assert!(base.join("setup/a.tmp").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_linux_targets_leave_claimed_and_special_entries() {
+        use std::os::unix::net::UnixListener;
+        let root = scratch_dir("linux");
+        let folders = VirtualRoot::new(&root);
+        let cache = folders.folder(KnownFolder::XdgCache).unwrap();
+        fs::create_dir_all(cache.join("thumbnails/large")).unwrap();
+        fs::write(cache.join("thumbnails/large/a.png"), b"12").unwrap();
+        fs::create_dir_all(cache.join("some-app")).unwrap();
+        fs::write(cache.join("some-app/b.bin"), b"1234").unwrap();
+        let _socket = UnixListener::bind(cache.join("some-app/ipc.sock")).unwrap();
+        // A link is removed itself, never followed
+        std::os::unix::fs::symlink(cache.join("thumbnails"), cache.join("elsewhere")).unwrap();
+
+        // With thumbnails switched off, the ~/.cache catch-all must not take them either
+        let mut cats = Categories::default();
+        cats.set("thumbnails", false);
+        let cfg = Config { categories: Some(cats), exact_stats: true, ..Config::default() };
+        let overrides = RunOverrides { folders: Some(Arc::new(folders)), ..Default::default() };
+        let preview = preview_targets(&cfg, &overrides);
+        let expected = [cache.join("elsewhere"), cache.join("some-app")];
+        assert_eq!(preview.target_dirs, expected.iter().map(|p| p.to_string_lossy().to_string()).collect::<Vec<_>>());
+
+        let summary = run_clean(&cfg, &overrides);
+        assert_eq!((summary.files_deleted, summary.links_removed, summary.bytes_freed), (1, 1, 4));
+        assert!(cache.join("thumbnails/large/a.png").exists());
+        assert!(cache.join("some-app/ipc.sock").exists());
+        assert!(!cache.join("elsewhere").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_tmp_keeps_session_entries() {
+        let root = scratch_dir("tmpfiles");
+        let folders = VirtualRoot::new(&root);
+        let tmp = folders.folder(KnownFolder::Temp).unwrap();
+        for file in [".X11-unix/X0", ".X0-lock", "systemd-private-abc-colord.service-x/tmp/a", "snap-private-tmp/b", "old/junk", "junk.bin"] {
+            fs::create_dir_all(tmp.join(file).parent().unwrap()).unwrap();
+            fs::write(tmp.join(file), b"1").unwrap();
+        }
+        // Without the rule's age limit, so only the kept entries stay
+        let mut cfg = Config::default();
+        cfg.policies.insert("tmp".into(), CategoryPolicy { min_age_hours: Some(0), ..Default::default() });
+        let overrides = RunOverrides { folders: Some(Arc::new(folders)), ..Default::default() };
+        let summary = run_clean(&cfg, &overrides);
+        assert_eq!(summary.exit_code(), 0);
+        assert!(!tmp.join("old").exists() && !tmp.join("junk.bin").exists());
+        assert_eq!(fs::read_dir(&tmp).unwrap().count(), 4);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_dev_caches_follow_family_toggle_and_env_override() {
+        let root = scratch_dir("devcaches");
+        let cargo_home = root.join("opt/cargo");
+        let folders = VirtualRoot::new(&root).with_env("CARGO_HOME", &cargo_home);
+        let home = folders.folder(KnownFolder::Home).unwrap();
+        fs::create_dir_all(cargo_home.join("registry/cache/index")).unwrap();
+        fs::write(cargo_home.join("registry/cache/index/a.crate"), b"1234").unwrap();
+        // Not used: CARGO_HOME points elsewhere
+        fs::create_dir_all(home.join(".cargo/registry/cache")).unwrap();
+        fs::create_dir_all(home.join(".npm/_cacache")).unwrap();
+        fs::create_dir_all(home.join(".m2/repository")).unwrap();
+
+        let overrides = RunOverrides { folders: Some(Arc::new(folders)), ..Default::default() };
+        // Off by default
+        assert!(preview_targets(&Config::default(), &overrides).target_dirs.is_empty());
+
+        let mut cats = Categories::default();
+        cats.set("dev_caches", true);
+        cats.set("npm_cache", false);
+        let cfg = Config { categories: Some(cats), ..Config::default() };
+        let expected = [home.join(".m2/repository"), cargo_home.join("registry/cache")];
+        let preview = preview_targets(&cfg, &overrides);
+        assert_eq!(preview.target_dirs, expected.iter().map(|p| p.to_string_lossy().to_string()).collect::<Vec<_>>());
+        assert_eq!(preview.bytes, 4);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_chromium_caches_only_in_listed_profiles() {
+        let root = scratch_dir("chromium");
+        let folders = VirtualRoot::new(&root);
+        // Windows keeps profiles and Local State in one folder; Linux puts the HTTP cache in ~/.cache
+        let (state, caches) = if cfg!(windows) {
+            let data = folders.folder(KnownFolder::LocalAppData).unwrap().join("Google/Chrome/User Data");
+            (data.clone(), data)
+        } else {
+            (folders.folder(KnownFolder::XdgConfig).unwrap().join("google-chrome"), folders.folder(KnownFolder::XdgCache).unwrap().join("google-chrome"))
+        };
+        fs::create_dir_all(&state).unwrap();
+        fs::write(state.join("Local State"), r#"{"profile":{"info_cache":{"Work":{"name":"Work (Acme)"}}}}"#).unwrap();
+        for dir in ["Work/Cache", "Crashpad/Cache", "Safe Browsing/Cache"] {
+            fs::create_dir_all(caches.join(dir)).unwrap();
+            fs::create_dir_all(state.join(dir)).unwrap();
+            fs::write(caches.join(dir).join("data_1"), b"12").unwrap();
+        }
+
+        let overrides = RunOverrides { folders: Some(Arc::new(folders)), ..Default::default() };
+        let preview = preview_targets(&Config::default(), &overrides);
+        let target = caches.join("Work/Cache").to_string_lossy().to_string();
+        assert_eq!(preview.target_dirs, vec![target.clone()]);
+        assert_eq!(preview.profiles.get(&target).map(String::as_str), Some("Work (Acme)"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_electron_apps_keep_storage_unless_enabled() {
+        let root = scratch_dir("electron");
+        let folders = VirtualRoot::new(&root);
+        let config = folders.folder(if cfg!(windows) { KnownFolder::AppData } else { KnownFolder::XdgConfig }).unwrap();
+        let app = config.join("SomeChat");
+        for dir in ["Cache/Cache_Data", "Partitions/work/GPUCache", "Local Storage/leveldb", "IndexedDB"] {
+            fs::create_dir_all(app.join(dir)).unwrap();
+        }
+        fs::write(app.join("Local State"), b"{}").unwrap();
+        fs::write(app.join("Preferences"), b"{}").unwrap();
+        // Same folders without the Electron markers are not an app's user data
+        fs::create_dir_all(config.join("notes/Cache")).unwrap();
+        // Nor is a browser's user data with the same markers at its top
+        let opera = config.join(if cfg!(windows) { "Opera Software/Opera Stable" } else { "opera" });
+        for dir in ["Cache", "Local Storage"] {
+            fs::create_dir_all(opera.join(dir)).unwrap();
+        }
+        fs::write(opera.join("Local State"), b"{}").unwrap();
+        fs::write(opera.join("Preferences"), b"{}").unwrap();
+
+        let overrides = RunOverrides { folders: Some(Arc::new(folders)), ..Default::default() };
+        let expected = [app.join("Cache"), app.join("Partitions/work/GPUCache")];
+        let preview = preview_targets(&Config::default(), &overrides);
+        assert_eq!(preview.target_dirs, expected.iter().map(|p| p.to_string_lossy().to_string()).collect::<Vec<_>>());
+        assert_eq!(preview.profiles.get(&*expected[1].to_string_lossy()).map(String::as_str), Some("SomeChat (work)"));
+
+        let mut cats = Categories::default();
+        cats.set("embedded_browser_storage", true);
+        let cfg = Config { categories: Some(cats), ..Config::default() };
+        let preview = preview_targets(&cfg, &overrides);
+        assert!(preview.target_dirs.contains(&app.join("Local Storage").to_string_lossy().to_string()));
+        assert!(preview.target_dirs.contains(&app.join("IndexedDB").to_string_lossy().to_string()));
+        assert!(!preview.target_dirs.iter().any(|d| Path::new(d).starts_with(&opera)));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_instances_selected_by_policy() {
+        let fx = rule_fixture("instances", serde_json::json!([
+            { "id": "apps", "category": "apps_cache", "path": "Packages/*/{LocalCache,TempState}", "instances": "Packages/*" }
+        ]));
+        fx.files(&[("Packages/Contoso.Notes_abc/LocalCache/data", 10), ("Packages/Contoso.Notes_abc/TempState/data", 5), ("Packages/Fabrikam.Maps_xyz/TempState/data", 300)]);
+        let (packages, overrides) = (fx.base.join("Packages"), &fx.overrides);
+        let mut cfg = Config { exact_stats: true, ..Config::default() };
+        cfg.policies.insert("apps_cache".into(), CategoryPolicy { exclude_instances: vec!["Fabrikam.*".into()], ..Default::default() });
+        let preview = preview_targets(&cfg, overrides);
+        assert_eq!(preview.target_dirs.len(), 2);
+        assert_eq!(preview.bytes, 15);
+        let sizes: Vec<(&str, bool, u64)> = preview.instances.iter().map(|i| (i.id.as_str(), i.selected, i.bytes)).collect();
+        assert_eq!(sizes, [("Contoso.Notes_abc", true, 15), ("Fabrikam.Maps_xyz", false, 300)]);
+
+        cfg.policies.insert("apps_cache".into(), CategoryPolicy { include_instances: vec!["Fabrikam.Maps_xyz".into()], ..Default::default() });
+        let summary = run_clean(&cfg, overrides);
+        assert_eq!(summary.bytes_freed, 300);
+        assert!(packages.join("Contoso.Notes_abc/TempState/data").exists());
+        let _ = fs::remove_dir_all(&fx.root);
+    }
+
+    #[test]
+    fn test_excluded_and_marked_entries_survive_fast_mode() {
+        let fx = rule_fixture("exclude", serde_json::json!([{ "id": "tool", "category": "tool_cache", "path": "Tool/{cache,marked}" }]));
+        fx.files(&[("Tool/cache/a.bin", 100), ("Tool/cache/sub/b.bin", 20), ("Tool/cache/x.lic", 10), ("Tool/cache/work/state", 50),
+            ("Tool/cache/work/.zentify-keep", 0), ("Tool/marked/.zentify-keep", 0), ("Tool/marked/c.bin", 5)]);
+        let (tool, overrides) = (fx.base.join("Tool"), &fx.overrides);
+        let cfg = Config { exclude: vec!["*.lic".into()], ..Config::default() };
+        let preview = preview_targets(&cfg, overrides);
+        assert_eq!(preview.target_dirs, vec![tool.join("cache").to_string_lossy().to_string()]);
+        assert_eq!(preview.bytes, 120);
+
+        let summary = run_clean(&cfg, overrides);
+        assert!(!tool.join("cache/a.bin").exists() && !tool.join("cache/sub").exists());
+        assert!(tool.join("cache/x.lic").exists() && tool.join("cache/work/state").exists() && tool.join("marked/c.bin").exists());
+        assert!(summary.failures.iter().any(|f| f.reason == FailureReason::Excluded && f.path == tool.join("marked").to_string_lossy()));
+        assert_eq!(summary.exit_code(), 0);
+        let _ = fs::remove_dir_all(&fx.root);
+    }
+
+    #[test]
+    fn test_custom_targets_follow_their_mode() {
+        let fx = rule_fixture("custom", serde_json::json!([{ "id": "team_all", "category": "team", "path": "Work/Team" }]));
+        fx.files(&[("Work/Team/other.bin", 4), ("Work/Team/Build/obj/x.o", 4), ("Work/Team/Fresh/recent.dat", 4), ("Work/Tool/logs/a.log", 4), ("Work/Tool/logs/keep.db", 4)]);
+        let var = if cfg!(windows) { "%LOCALAPPDATA%" } else { "%XDG_CACHE_HOME%" };
+        let (dir, overrides) = (&fx.base, &fx.overrides);
+
+        let custom: Vec<CustomTarget> = serde_json::from_value(serde_json::json!([
+            { "path": format!("{}/Work/Team/Build", var) },
+            { "path": format!("{}/Work/Team/Fresh", var), "mode": "age", "max_age_hours": 1 },
+            { "path": format!("{}/Work/Tool/logs", var), "mode": "glob", "glob": "*.log" },
+            { "path": "/etc/ssl", "mode": "purge" },
+            { "path": format!("{}/Work/../..", var) },
+        ])).unwrap();
+        let cfg = Config { custom_targets: custom, exact_stats: true, ..Config::default() };
+        // Paths outside a known folder or leaving it are refused
+        assert_eq!(cfg.custom_rules().iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), ["custom_1", "custom_2", "custom_3"]);
+
+        let preview = preview_targets(&cfg, overrides);
+        assert_eq!(preview.target_files, vec![dir.join("Work/Tool/logs/a.log").to_string_lossy().to_string()]);
+        // Team is cleaned around the custom targets inside it; the fresh file is not old enough
+        assert_eq!(preview.bytes, 12);
+
+        let summary = run_clean(&cfg, overrides);
+        assert_eq!(summary.bytes_freed, 12);
+        assert!(!dir.join("Work/Team/other.bin").exists() && !dir.join("Work/Team/Build/obj").exists() && !dir.join("Work/Tool/logs/a.log").exists());
+        // A purged custom folder stays for the app that writes to it
+        assert!(dir.join("Work/Team/Build").is_dir());
+        assert!(dir.join("Work/Team/Fresh/recent.dat").exists() && dir.join("Work/Tool/logs/keep.db").exists());
+        let _ = fs::remove_dir_all(&fx.root);
+    }
+
+    #[test]
+    fn test_custom_target_in_guid_folder() {
+        let fx = rule_fixture("customguid", serde_json::json!([]));
+        fx.files(&[("Vendor/{3F2504E0-4F89-11D3-9A0C-0305E82C3301}/Cache/a", 6)]);
+        let var = if cfg!(windows) { "%LOCALAPPDATA%" } else { "%XDG_CACHE_HOME%" };
+        let custom: Vec<CustomTarget> = serde_json::from_value(serde_json::json!([
+            { "path": format!("{}/Vendor/{{3F2504E0-4F89-11D3-9A0C-0305E82C3301}}/Cache", var) },
+        ])).unwrap();
+        let cfg = Config { custom_targets: custom, exact_stats: true, ..Config::default() };
+        assert_eq!(run_clean(&cfg, &fx.overrides).bytes_freed, 6);
+        assert!(fx.base.join("Vendor/{3F2504E0-4F89-11D3-9A0C-0305E82C3301}/Cache").is_dir());
+        let _ = fs::remove_dir_all(&fx.root);
+    }
+
+    #[test]
+    fn test_custom_chromium_user_data() {
+        let fx = rule_fixture("customchromium", serde_json::json!([]));
+        fx.files(&[("Relocated/Work/Cache/data_0", 7), ("Relocated/Work/Bookmarks", 3), ("Relocated/Crashpad/Cache/x", 5)]);
+        fs::write(fx.base.join("Relocated/Local State"), r#"{"profile":{"info_cache":{"Work":{"name":"Work"}}}}"#).unwrap();
+        let var = if cfg!(windows) { "%LOCALAPPDATA%" } else { "%XDG_CACHE_HOME%" };
+        let custom: Vec<CustomTarget> = serde_json::from_value(serde_json::json!([
+            { "path": format!("{}/Relocated", var), "mode": "chromium", "category": "browser_cache" },
+        ])).unwrap();
+        let cfg = Config { custom_targets: custom, exact_stats: true, ..Config::default() };
+
+        // Only the listed profile's caches, not lookalike folders next to it
+        let cache = fx.base.join("Relocated/Work/Cache").to_string_lossy().to_string();
+        let preview = preview_targets(&cfg, &fx.overrides);
+        assert_eq!(preview.target_dirs, vec![cache.clone()]);
+        assert_eq!(preview.profiles.get(&cache).map(String::as_str), Some("Work"));
+        assert_eq!(run_clean(&cfg, &fx.overrides).bytes_freed, 7);
+        assert!(fx.base.join("Relocated/Work/Bookmarks").exists() && fx.base.join("Relocated/Crashpad/Cache/x").exists());
+        let _ = fs::remove_dir_all(&fx.root);
+    }
+
+    #[test]
+    fn test_broad_rules_clean_around_nested_and_yield_to_specific() {
+        let fx = rule_fixture("nested", serde_json::json!([
+            { "id": "tool_all", "category": "tool_all", "path": "Tool/*" },
+            { "id": "tool_webview", "category": "tool_webview", "path": "Tool/app/EBWebView", "default_enabled": false },
+            { "id": "tool_logs", "category": "tool_logs", "path": "Tool/logs" },
+            { "id": "box", "category": "box", "path": "Box" },
+            { "id": "box_app", "category": "box_app", "path": "Box/*", "profiles": { "layout": "electron", "path": "Cache" } },
+        ]));
+        fx.files(&[("Tool/app/junk.bin", 10), ("Tool/app/EBWebView/state", 20), ("Tool/logs/new.log", 5),
+            ("Box/junk.bin", 1), ("Box/chat/Local State", 2), ("Box/chat/Preferences", 2), ("Box/chat/Cache/c", 3)]);
+        let (tool, chat) = (fx.base.join("Tool"), fx.base.join("Box/chat"));
+        // The specific rule's retention applies to Tool/logs, not the broad rule's purge
+        let mut cfg = Config { exact_stats: true, ..Config::default() };
+        cfg.policies.insert("tool_logs".into(), CategoryPolicy { min_age_hours: Some(1), ..Default::default() });
+        let preview = preview_targets(&cfg, &fx.overrides);
+        // Box is cleaned around the Electron app inside it, whose cache the app rule takes
+        let expected = [fx.base.join("Box"), chat.join("Cache"), tool.join("app")];
+        assert_eq!(preview.target_dirs, expected.iter().map(|p| p.to_string_lossy().to_string()).collect::<Vec<_>>());
+        assert_eq!(preview.bytes, 14);
+
+        let summary = run_clean(&cfg, &fx.overrides);
+        assert_eq!(summary.bytes_freed, 14);
+        assert!(!tool.join("app/junk.bin").exists() && !fx.base.join("Box/junk.bin").exists() && !chat.join("Cache").exists());
+        assert!(tool.join("app/EBWebView/state").exists() && tool.join("logs/new.log").exists() && chat.join("Preferences").exists());
+        let _ = fs::remove_dir_all(&fx.root);
+    }
+
+    #[test]
+    fn test_hardlinked_data_counted_once() {
+        let root = scratch_dir("hardlinks");
+        let target = root.join("target");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("a"), b"1234").unwrap();
+        fs::hard_link(target.join("a"), target.join("b")).unwrap();
+        fs::write(target.join("c"), b"12").unwrap();
+        fs::hard_link(target.join("c"), root.join("outside")).unwrap();
+        let count = compute_dir_stats(&target, &EntryFilter::default(), 2);
+        // a/b share their data and both go; c keeps a link outside the target
+        assert_eq!((count.files, count.bytes, count.unfreed), (3, 4, 2));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_gecko_profile_with_custom_path_cleaned() {
+        let fx = rule_fixture("geckocustom", serde_json::json!([
+            { "id": "fox", "category": "browser_cache", "path": "Fox", "profiles": { "layout": "gecko", "path": "cache2" } }
+        ]));
+        // Profiles outside every allowed base; only the one holding a prefs.js is a profile
+        let (work, stray) = (fx.root.join("elsewhere/work"), fx.root.join("elsewhere/stray"));
+        for (dir, file, size) in [(&work, "cache2/entries/a", 9), (&work, "prefs.js", 1), (&stray, "cache2/entries/b", 4)] {
+            fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            fs::write(dir.join(file), vec![0u8; size]).unwrap();
+        }
+        fx.files(&[("Fox/Profiles/a1.default/cache2/c", 3)]);
+        let ini = format!("[Profile0]\nName=default\nIsRelative=1\nPath=Profiles/a1.default\n\n[Profile1]\nName=Work\nIsRelative=0\nPath={}\n\n\
+             [Profile2]\nName=Stray\nIsRelative=0\nPath={}\n", work.display(), stray.display());
+        fs::write(fx.base.join("Fox/profiles.ini"), ini).unwrap();
+
+        let cfg = Config { exact_stats: true, ..Config::default() };
+        assert_eq!(preview_targets(&cfg, &fx.overrides).bytes, 12);
+        assert_eq!(run_clean(&cfg, &fx.overrides).bytes_freed, 12);
+        assert!(!work.join("cache2/entries/a").exists() && work.join("prefs.js").exists());
+        assert!(stray.join("cache2/entries/b").exists());
+        let _ = fs::remove_dir_all(&fx.root);
+    }
+
+    #[test]
+    fn test_volume_quarantine_folders_never_cleaned() {
+        let fx = rule_fixture("volumequarantine", serde_json::json!([
+            { "id": "tool", "category": "tool_cache", "path": "Tool" },
+            { "id": "stashed", "category": "tool_cache", "path": "Tool/.zentify-quarantine/*" },
+        ]));
+        fx.files(&[("Tool/a.bin", 10), ("Tool/.zentify-quarantine/1-run/0-b.bin", 5)]);
+        let cfg = Config::default();
+        let preview = preview_targets(&cfg, &fx.overrides);
+        assert_eq!((preview.target_dirs.len(), preview.bytes), (1, 10));
+        run_clean(&cfg, &fx.overrides);
+        // The fast path cleans around the quarantine folder instead of removing the whole tree
+        assert!(!fx.base.join("Tool/a.bin").exists() && fx.base.join("Tool/.zentify-quarantine/1-run/0-b.bin").exists());
+        let _ = fs::remove_dir_all(&fx.root);
+    }
+
+    #[test]
+    fn test_hardlinks_booked_only_when_removed() {
+        let fx = rule_fixture("linkbooking", serde_json::json!([
+            { "id": "trimmed", "category": "trimmed_cache", "path": "Tool/trimmed" },
+            { "id": "moved", "category": "moved_cache", "path": "Tool/moved" },
+        ]));
+        fx.files(&[("Tool/trimmed/a", 1000), ("Tool/moved/c", 300)]);
+        fs::hard_link(fx.base.join("Tool/trimmed/a"), fx.base.join("Tool/trimmed/b")).unwrap();
+        fs::hard_link(fx.base.join("Tool/moved/c"), fx.base.join("Tool/moved/d")).unwrap();
+
+        // The trim keeps everything, the quarantine takes both links of c at once
+        let mut cfg = Config { quarantine: true, ..Config::default() };
+        cfg.policies.insert("trimmed_cache".into(), CategoryPolicy { action: CleanAction::Trim, keep_mb: Some(1000), ..Default::default() });
+        let summary = run_clean(&cfg, &fx.overrides);
+        assert_eq!((summary.files_deleted, summary.bytes_freed, summary.bytes_not_freed), (2, 300, 0));
+        assert!(fx.base.join("Tool/trimmed/b").exists() && !fx.base.join("Tool/moved").exists());
+
+        // Evicting both links frees the data once
+        cfg.policies.insert("trimmed_cache".into(), CategoryPolicy { action: CleanAction::Trim, keep_mb: Some(0), ..Default::default() });
+        assert_eq!(preview_targets(&cfg, &fx.overrides).bytes, 1000);
+        let summary = run_clean(&cfg, &fx.overrides);
+        assert_eq!((summary.files_deleted, summary.bytes_freed), (2, 1000));
+        let _ = fs::remove_dir_all(&fx.root);
+    }
+
+    #[test]
+    fn test_trim_without_keep_mb_is_skipped() {
+        let fx = rule_fixture("trimnokeep", serde_json::json!([
+            { "id": "trimmed", "category": "trimmed_cache", "path": "Tool/trimmed" },
+            { "id": "outer", "category": "outer_cache", "path": "Tool" },
+        ]));
+        fx.files(&[("Tool/trimmed/a", 100), ("Tool/other/b", 10)]);
+        let mut cfg = Config::default();
+        cfg.policies.insert("trimmed_cache".into(), CategoryPolicy { action: CleanAction::Trim, ..Default::default() });
+        assert_eq!(preview_targets(&cfg, &fx.overrides).bytes, 10);
+        run_clean(&cfg, &fx.overrides);
+        // Neither purged nor removed with the outer target
+        assert!(fx.base.join("Tool/trimmed/a").exists() && !fx.base.join("Tool/other").exists());
+        let _ = fs::remove_dir_all(&fx.root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_allocated_size_of_sparse_file() {
+        let root = scratch_dir("allocated");
+        fs::write(root.join("small.bin"), b"x").unwrap();
+        // A hole: a megabyte of logical size without any data blocks
+        fs::File::create(root.join("sparse.bin")).unwrap().set_len(1 << 20).unwrap();
+        let count = compute_dir_stats(&root, &EntryFilter::default(), 2);
+        assert_eq!(count.bytes, (1 << 20) + 1);
+        assert!(count.allocated < 1 << 20);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
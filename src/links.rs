@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Identity of a file's data, shared by all of its hard links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct FileId {
+    /// Device (Unix) or volume serial number (Windows)
+    dev: u64,
+    /// Inode (Unix) or file index (Windows)
+    ino: u64,
+}
+
+/// Identity and link count of `p` when it has more than one hard link.
+#[cfg(unix)]
+pub(crate) fn hardlink_id(_p: &Path, md: &fs::Metadata) -> Option<(FileId, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (md.nlink() > 1).then(|| (FileId { dev: md.dev(), ino: md.ino() }, md.nlink()))
+}
+
+/// Identity and link count of `p` when it has more than one hard link.
+/// The listing does not carry the link count on Windows, so this opens the file (attributes access only).
+#[cfg(windows)]
+pub(crate) fn hardlink_id(p: &Path, _md: &fs::Metadata) -> Option<(FileId, u64)> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT,
+        FILE_READ_ATTRIBUTES, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE,
+    };
+    let f = fs::OpenOptions::new()
+        .access_mode(FILE_READ_ATTRIBUTES)
+        .share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+        .open(p)
+        .ok()?;
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(f.as_raw_handle() as _, &mut info) } == 0 { return None; }
+    let ino = ((info.nFileIndexHigh as u64) << 32) | info.nFileIndexLow as u64;
+    (info.nNumberOfLinks > 1).then(|| (FileId { dev: info.dwVolumeSerialNumber as u64, ino }, info.nNumberOfLinks as u64))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn hardlink_id(_p: &Path, _md: &fs::Metadata) -> Option<(FileId, u64)> { None }
+
+struct Linked {
+    nlink: u64,
+    seen: u64,
+    bytes: u64,
+    allocated: u64,
+}
+
+/// Space of hard-linked files after a walk: data is counted once, and only if every link went.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct LinkTotals {
+    /// Logical and on-disk size of files whose last link was removed
+    pub(crate) freed_bytes: u64,
+    pub(crate) freed_allocated: u64,
+    /// Logical size of files that keep at least one link elsewhere
+    pub(crate) unfreed_bytes: u64,
+}
+
+/// Collects removed (or counted) links of multiply-linked files.
+#[derive(Default)]
+pub(crate) struct LinkTracker {
+    seen: Mutex<HashMap<FileId, Linked>>,
+}
+
+impl LinkTracker {
+    pub(crate) fn record(&self, id: FileId, nlink: u64, bytes: u64, allocated: u64) {
+        if let Ok(mut m) = self.seen.lock() {
+            m.entry(id).or_insert(Linked { nlink, seen: 0, bytes, allocated }).seen += 1;
+        }
+    }
+
+    pub(crate) fn totals(&self) -> LinkTotals {
+        let mut t = LinkTotals::default();
+        let Ok(m) = self.seen.lock() else { return t };
+        for l in m.values() {
+            if l.seen >= l.nlink {
+                t.freed_bytes += l.bytes;
+                t.freed_allocated += l.allocated;
+            } else {
+                t.unfreed_bytes += l.bytes;
+            }
+        }
+        t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hardlinks_count_once_and_only_when_all_links_go() {
+        let dir = std::env::temp_dir().join(format!("zentify-test-links-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a"), b"12345").unwrap();
+        fs::hard_link(dir.join("a"), dir.join("b")).unwrap();
+        fs::write(dir.join("single"), b"1").unwrap();
+        let md = fs::metadata(dir.join("a")).unwrap();
+        let (id, nlink) = hardlink_id(&dir.join("a"), &md).unwrap();
+        assert_eq!(nlink, 2);
+        assert_eq!(hardlink_id(&dir.join("b"), &fs::metadata(dir.join("b")).unwrap()).unwrap().0, id);
+        assert!(hardlink_id(&dir.join("single"), &fs::metadata(dir.join("single")).unwrap()).is_none());
+
+        let tracker = LinkTracker::default();
+        tracker.record(id, nlink, 5, 4096);
+        assert_eq!(tracker.totals(), LinkTotals { unfreed_bytes: 5, ..LinkTotals::default() });
+        tracker.record(id, nlink, 5, 4096);
+        assert_eq!(tracker.totals(), LinkTotals { freed_bytes: 5, freed_allocated: 4096, unfreed_bytes: 0 });
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
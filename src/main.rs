@@ -1,39 +1,69 @@
-
-use std::fs;
-use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(windows)]
 use std::io::{self, Write};
-use clap::Parser;
-use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use clap::{Parser, Subcommand};
 use log::{debug, info};
 use zentify_cleaner::{
-    load_config as core_load_config,
+    find_artifacts,
+    load_config_from as core_load_config_from,
     run_clean as core_run_clean,
     RunOverrides as CoreRunOverrides,
+    Config,
     env_truthy,
     is_elevated,
     format_bytes,
+    quarantine,
+    CancellationToken,
+    CleanFailure,
+    CleanEvent,
+    CleanObserver,
+    EntryKind,
+    KnownFolders,
+    SystemFolders,
+    VirtualRoot,
+    Artifact,
+    ArtifactSweep,
+    discover_caches,
+    DiscoverOptions,
+    Discovery,
+    RuleCatalog,
 };
 
 #[cfg(windows)]
-use std::os::windows::fs::MetadataExt;
-#[cfg(windows)]
-use windows_sys::Win32::System::Console::{GetConsoleWindow, GetConsoleProcessList};
+use windows_sys::Win32::System::Console::{GetConsoleWindow, GetConsoleProcessList, SetConsoleCtrlHandler};
 
-#[cfg(windows)]
 fn main() {
     // Parse CLI flags
     let cli = Cli::parse();
 
+    // A virtual root rehearses everything (config, rules, targets, quarantine) against a fake profile tree
+    let folders: Arc<dyn KnownFolders> = match &cli.virtual_root {
+        Some(root) => Arc::new(VirtualRoot::new(root)),
+        None => Arc::new(SystemFolders),
+    };
+
     // Load configuration (optional) and apply CLI overrides (CLI > Env > Config)
-    let mut cfg = core_load_config();
+    let mut cfg = core_load_config_from(&*folders);
     if cli.dry_run { cfg.dry_run = true; }
     if cli.verbose { cfg.verbose = true; cfg.quiet = false; }
     if cli.quiet { cfg.quiet = true; cfg.verbose = false; }
     if cli.exact_stats { cfg.exact_stats = true; }
+    if cli.quarantine { cfg.quarantine = true; }
 
     init_logging(cfg.quiet, cfg.verbose);
 
+    // Maintenance subcommands do not clean anything; a sweep is a run with other targets
+    let sweep = match cli.command {
+        Some(Command::Sweep { roots, idle }) => Some(ArtifactSweep { roots, min_idle: idle }),
+        Some(Command::Maintenance(cmd)) => {
+            let code = run_command(cmd, &cfg, &*folders);
+            if should_pause_on_exit() { pause_console(); }
+            std::process::exit(code);
+        }
+        None => None,
+    };
+
     // Determine if system-level cleaning is allowed
     let mut allow_system = env_truthy("ZENTIFY_ALLOW_SYSTEM_CLEAN");
     if !allow_system && is_elevated() { allow_system = true; }
@@ -44,7 +74,17 @@ fn main() {
     // Build overrides from env toggles
     let prefetch_override = if env_truthy("ZENTIFY_PREFETCH") { Some(true) } else { None };
     let max_par = std::env::var("ZENTIFY_MAX_PARALLELISM").ok().and_then(|s| s.parse::<usize>().ok());
-    let overrides = CoreRunOverrides { allow_system, prefetch: prefetch_override, max_parallelism: max_par };
+    if let Some(s) = sweep.as_ref().filter(|_| !cfg.quiet) {
+        print_artifacts(&find_artifacts(s, max_par.unwrap_or(8)));
+    }
+    let mut overrides = CoreRunOverrides { allow_system, prefetch: prefetch_override, max_parallelism: max_par, target_free_bytes: cli.target_free, folders: Some(folders), sweep, ..Default::default() };
+    if cfg.verbose && !cfg.quiet {
+        overrides.observer = Some(Arc::new(ConsoleObserver { dry_run: cfg.dry_run }));
+    }
+    // Ctrl+C stops the run between entries instead of killing it mid-way
+    let cancel = CancellationToken::new();
+    install_ctrl_c_handler(cancel.clone());
+    overrides.cancel = Some(cancel);
 
     // Execute cleaning via library
     let summary = core_run_clean(&cfg, &overrides);
@@ -52,39 +92,82 @@ fn main() {
     if !cfg.quiet {
         if cfg.dry_run {
             println!(
-                "Dry-run summary: would remove {} files, {} dirs, {} links; free approx {} ({} bytes) in {:?}.",
+                "Dry-run summary: would remove {} files, {} dirs, {} links; free approx {} ({} bytes, {} on disk) in {:?}.",
                 summary.files_deleted,
                 summary.dirs_deleted,
                 summary.links_removed,
                 format_bytes(summary.bytes_freed),
                 summary.bytes_freed,
+                format_bytes(summary.bytes_allocated),
                 summary.elapsed
             );
         } else {
             println!(
-                "Summary: removed {} files, {} dirs, {} links; freed {} ({} bytes) in {:?}.",
+                "Summary: removed {} files, {} dirs, {} links; freed {} ({} bytes, {} on disk) in {:?}.",
                 summary.files_deleted,
                 summary.dirs_deleted,
                 summary.links_removed,
                 format_bytes(summary.bytes_freed),
                 summary.bytes_freed,
+                format_bytes(summary.bytes_allocated),
                 summary.elapsed
             );
             if !summary.exact_stats {
                 println!("Note: Byte counts for directories are approximate (fast mode). Use --exact-stats for precise totals.");
             }
+            if let Some(q) = &summary.quarantine {
+                println!("Quarantined to {} (undo with: zentify-cleaner restore {})", q.path, q.run_id);
+            }
+            if summary.bytes_not_freed > 0 {
+                println!("Not actually freed: {} of hard-linked files still reachable through other links.", format_bytes(summary.bytes_not_freed));
+            }
+            for v in &summary.volumes {
+                println!("Volume {}", v);
+            }
+        }
+        if let Some(b) = &summary.budget {
+            println!("{}", b);
+        }
+        print_failures(&summary.failures, cfg.verbose);
+        if summary.cancelled {
+            println!("Run cancelled; the summary covers only what was processed before the interruption.");
+        } else {
+            println!("Aggressive cleaning complete.");
         }
-        println!("Aggressive cleaning complete.");
     }
 
     // If launched from Explorer (own console), keep window open until user presses Enter
     if should_pause_on_exit() { pause_console(); }
+    // 0 = clean, 3 = some entries could not be removed, 130 = cancelled
+    let code = summary.exit_code();
+    if code != 0 { std::process::exit(code); }
+}
+
+fn print_artifacts(found: &[Artifact]) {
+    let total: u64 = found.iter().map(|a| a.bytes_allocated).sum();
+    println!("Found {} build output directories of idle projects ({} on disk):", found.len(), format_bytes(total));
+    for a in found {
+        println!("  {} ({}, {})", a.path.display(), a.category, format_bytes(a.bytes_allocated));
+    }
+}
+
+fn print_failures(failures: &[CleanFailure], verbose: bool) {
+    const SHOWN: usize = 10;
+    if failures.is_empty() { return; }
+    eprintln!("{} entries could not be cleaned:", failures.len());
+    let limit = if verbose { failures.len() } else { SHOWN };
+    for f in failures.iter().take(limit) {
+        eprintln!("  {}", f);
+    }
+    if failures.len() > limit {
+        eprintln!("  ... and {} more (use --verbose to list all)", failures.len() - limit);
+    }
 }
 
 // ---------- CLI ----------
 
 #[derive(Debug, Parser)]
-#[command(name = "zentify-cleaner", version, author, about = "Minimal, fast Windows temp cleaner (Windows 10/11)")]
+#[command(name = "zentify-cleaner", version, author, about = "Minimal, fast temp cleaner (Windows 10/11 and Linux)")]
 struct Cli {
     /// Do not delete anything, only print what would be deleted
     #[arg(long)]
@@ -98,583 +181,247 @@ struct Cli {
     #[arg(long)]
     quiet: bool,
 
-    /// Compute exact freed byte counts (slower)
+    /// Compute exact freed byte counts (deletes entry by entry in a single pass)
     #[arg(long)]
     exact_stats: bool,
-}
-
-fn init_logging(quiet: bool, verbose: bool) {
-    let default_level = if quiet {
-        "error"
-    } else if verbose {
-        "debug"
-    } else {
-        "info"
-    };
-    let env = env_logger::Env::default().default_filter_or(default_level);
-    let _ = env_logger::Builder::from_env(env).is_test(false).try_init();
-    debug!("Logger initialized with level: {}", default_level);
-    info!("Starting Zentify Cleaner");
-}
 
-fn dedup_paths(v: &mut Vec<PathBuf>) {
-    v.retain(|p| !p.as_os_str().is_empty());
-    v.sort();
-    v.dedup();
-}
+    /// Move targets into a per-run quarantine folder instead of deleting them
+    #[arg(long)]
+    quarantine: bool,
 
-/// Build a conservative list of allowed root prefixes under which we will operate.
-/// This is an extra safety net on top of explicit path selection and sensitive-dir checks.
-fn allowed_prefixes(allow_system: bool) -> Vec<PathBuf> {
-    let mut bases: Vec<PathBuf> = Vec::new();
-    // User-scoped bases
-    bases.push(std::env::temp_dir());
-    if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
-        bases.push(PathBuf::from(localappdata));
-    }
-    if let Ok(appdata) = std::env::var("APPDATA") {
-        bases.push(PathBuf::from(appdata));
-    }
-    // System-scoped bases (only if explicitly allowed)
-    if allow_system {
-        if let Ok(windir) = std::env::var("WINDIR") { bases.push(PathBuf::from(windir)); }
-        if let Ok(systemroot) = std::env::var("SystemRoot") { bases.push(PathBuf::from(systemroot)); }
-        if let Ok(programdata) = std::env::var("ProgramData") { bases.push(PathBuf::from(programdata)); }
-    }
-    // Normalize: keep only absolute, existing directories, dedup
-    bases.retain(|p| p.is_dir());
-    bases.sort();
-    bases.dedup();
-    bases
-}
+    /// Only clean the lowest-risk categories needed to free this much (e.g. 10G, 500M)
+    #[arg(long, value_parser = parse_size)]
+    target_free: Option<u64>,
 
-fn canonicalize_ok(p: &Path) -> Option<PathBuf> { p.canonicalize().ok() }
+    /// Resolve every well-known folder below this directory (rehearsal against a fake profile tree)
+    #[arg(long, value_name = "DIR")]
+    virtual_root: Option<std::path::PathBuf>,
 
-fn is_under(path: &Path, base: &Path) -> bool {
-    match (canonicalize_ok(path), canonicalize_ok(base)) {
-        (Some(cp), Some(cb)) => cp.starts_with(cb),
-        _ => false,
-    }
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn retain_allowed_paths(v: &mut Vec<PathBuf>, allow_system: bool) {
-    let bases = allowed_prefixes(allow_system);
-    if bases.is_empty() { return; }
-    v.retain(|p| bases.iter().any(|b| is_under(p, b)));
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Maintenance(Maintenance),
+    /// Clean build output (target/, node_modules, bin/obj, ...) of projects idle for a while
+    Sweep {
+        /// Workspace roots to search
+        #[arg(required = true)]
+        roots: Vec<std::path::PathBuf>,
+        /// Only projects with nothing modified for this long (e.g. 30d, 12h)
+        #[arg(long, value_parser = parse_age, default_value = "30d")]
+        idle: Duration,
+    },
 }
 
-fn candidate_dirs(cats: &Categories, allow_system: bool) -> Vec<PathBuf> {
-    let mut v: Vec<PathBuf> = Vec::new();
-
-    // User temp
-    if cats.user_temp {
-        v.push(std::env::temp_dir());
-        if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
-            v.push(Path::new(&localappdata).join("Temp"));
-            if cats.directx_cache { v.push(Path::new(&localappdata).join("D3DSCache")); }
-            if cats.temp_internet_files {
-                v.push(Path::new(&localappdata).join("Microsoft/Windows/INetCache"));
-                // Legacy WebCache (ESE) used by IE/Legacy Edge/Explorer
-                v.push(Path::new(&localappdata).join("Microsoft/Windows/WebCache"));
-            }
-            if cats.crash_dumps {
-                v.push(Path::new(&localappdata).join("CrashDumps"));
-            }
-            // User-level WER
-            if cats.error_reports {
-                v.push(Path::new(&localappdata).join("Microsoft/Windows/WER/ReportQueue"));
-                v.push(Path::new(&localappdata).join("Microsoft/Windows/WER/ReportArchive"));
-                v.push(Path::new(&localappdata).join("Microsoft/Windows/WER/Temp"));
-            }
-            // Windows Widgets (WebExperience) cache
-            if cats.widgets_cache {
-                let widgets_pkg = Path::new(&localappdata).join("Packages/MicrosoftWindows.Client.WebExperience_cw5n1h2txyewy");
-                v.push(widgets_pkg.join("LocalCache"));
-                v.push(widgets_pkg.join("TempState"));
-            }
-            // Teams (Classic) caches
-            if cats.teams_cache {
-                v.push(Path::new(&localappdata).join("Packages/MSTeams_8wekyb3d8bbwe/LocalCache")); // New Teams (UWP)
-            }
-            // Office Document Cache
-            if cats.office_cache {
-                v.push(Path::new(&localappdata).join("Microsoft/Office/16.0/OfficeFileCache"));
-            }
-            // NVIDIA shader caches (tie to directx_cache category)
-            if cats.directx_cache {
-                v.push(Path::new(&localappdata).join("NVIDIA/GLCache"));
-                v.push(Path::new(&localappdata).join("NVIDIA/DXCache"));
-            }
-            // Windows Media Player cache (best-effort)
-            if cats.wmp_cache {
-                v.push(Path::new(&localappdata).join("Microsoft/Media Player/Cache"));
-            }
-            // Java cache
-            if cats.java_cache {
-                v.push(Path::new(&localappdata).join("Sun/Java/Deployment/cache"));
-            }
-            // Adobe media caches (common locations)
-            if cats.adobe_cache {
-                v.push(Path::new(&localappdata).join("Adobe/Common/Media Cache"));
-                v.push(Path::new(&localappdata).join("Adobe/Common/Media Cache Files"));
-            }
-        }
-        if let Ok(appdata) = std::env::var("APPDATA") {
-            if cats.teams_cache {
-                let teams_root = Path::new(&appdata).join("Microsoft/Teams");
-                v.push(teams_root.join("Cache"));
-                v.push(teams_root.join("GPUCache"));
-                v.push(teams_root.join("Service Worker/CacheStorage"));
-                v.push(teams_root.join("IndexedDB"));
-                v.push(teams_root.join("Local Storage"));
-            }
-        }
-    }
-
-    // System-level only if allowed explicitly
-    if allow_system {
-        if let Ok(windir) = std::env::var("WINDIR") {
-            if cats.windows_temp { v.push(Path::new(&windir).join("Temp")); }
-            if cats.prefetch { v.push(Path::new(&windir).join("Prefetch")); }
-            if cats.windows_update { v.push(Path::new(&windir).join("SoftwareDistribution/Download")); }
-            if cats.crash_dumps {
-                v.push(Path::new(&windir).join("Minidump"));
-                v.push(Path::new(&windir).join("LiveKernelReports"));
-            }
-            if cats.aspnet_temp {
-                v.push(Path::new(&windir).join("Microsoft.NET/Framework/v4.0.30319/Temporary ASP.NET Files"));
-                v.push(Path::new(&windir).join("Microsoft.NET/Framework64/v4.0.30319/Temporary ASP.NET Files"));
-            }
-        }
-        if let Ok(systemroot) = std::env::var("SystemRoot") {
-            if cats.windows_temp { v.push(Path::new(&systemroot).join("Temp")); }
-            if cats.windows_update { v.push(Path::new(&systemroot).join("SoftwareDistribution/Download")); }
-        }
-        if let Ok(programdata) = std::env::var("ProgramData") {
-            if cats.delivery_optimization { v.push(Path::new(&programdata).join("Microsoft/Windows/DeliveryOptimization/Cache")); }
-            if cats.error_reports {
-                v.push(Path::new(&programdata).join("Microsoft/Windows/WER/ReportQueue"));
-                v.push(Path::new(&programdata).join("Microsoft/Windows/WER/ReportArchive"));
-                v.push(Path::new(&programdata).join("Microsoft/Windows/WER/Temp"));
-            }
-            if cats.defender_cache {
-                v.push(Path::new(&programdata).join("Microsoft/Windows Defender/Scans/History"));
-            }
-        }
-    }
+/// Subcommands that do not clean anything.
+#[derive(Debug, Subcommand)]
+enum Maintenance {
+    /// Move everything from a quarantine run back to its original location
+    Restore {
+        /// Run id as printed after a --quarantine run
+        run_id: String,
+    },
+    /// List cache-like folders no rule covers, with rule entries to promote into a rule file
+    Discover {
+        /// Folder levels searched below each root
+        #[arg(long, default_value_t = 4)]
+        depth: usize,
+        /// Ignore folders smaller than this on disk (e.g. 10M, 1G)
+        #[arg(long, value_parser = parse_size, default_value = "10M")]
+        min_size: u64,
+        /// Print only the rule file (JSON)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Permanently delete quarantine runs
+    PurgeQuarantine {
+        /// Only purge runs older than this (e.g. 7d, 48h, 30m; plain numbers are seconds)
+        #[arg(long, value_parser = parse_age, default_value = "7d")]
+        older_than: Duration,
+    },
+}
 
-    // Modern Apps (UWP) LocalCache/TempState
-    if cats.modern_apps_cache {
-        if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
-            let packages = Path::new(&localappdata).join("Packages");
-            if packages.is_dir() {
-                if let Ok(rd) = fs::read_dir(&packages) {
-                    for e in rd.flatten() {
-                        let p = e.path();
-                        if p.is_dir() {
-                            v.push(p.join("LocalCache"));
-                            v.push(p.join("TempState"));
-                        }
-                    }
-                }
-            }
-        }
-    }
+fn parse_age(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (num, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let n: u64 = num.parse().map_err(|_| format!("invalid duration: {}", s))?;
+    let secs = match unit {
+        "" | "s" => n,
+        "m" => n * 60,
+        "h" => n * 3600,
+        "d" => n * 86400,
+        _ => return Err(format!("invalid duration unit in {} (use s, m, h or d)", s)),
+    };
+    Ok(Duration::from_secs(secs))
+}
 
-    // Keep only existing directories
-    v.retain(|p| p.is_dir());
-    v
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (num, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let n: u64 = num.parse().map_err(|_| format!("invalid size: {}", s))?;
+    let shift = match unit.trim_end_matches(['B', 'b']) {
+        "" => 0,
+        "K" | "k" => 10,
+        "M" | "m" => 20,
+        "G" | "g" => 30,
+        "T" | "t" => 40,
+        _ => return Err(format!("invalid size unit in {} (use K, M, G or T)", s)),
+    };
+    n.checked_mul(1 << shift).ok_or_else(|| format!("size too large: {}", s))
 }
 
-fn candidate_files(cats: &Categories, allow_system: bool) -> Vec<PathBuf> {
-    let mut v: Vec<PathBuf> = Vec::new();
-    if cats.thumbnails {
-        if let Ok(localappdata) = std::env::var("LOCALAPPDATA") {
-            let explorer = Path::new(&localappdata).join("Microsoft/Windows/Explorer");
-            if explorer.is_dir() {
-                if let Ok(rd) = fs::read_dir(&explorer) {
-                    for e in rd.flatten() {
-                        let p = e.path();
-                        if let Some(name) = p.file_name().and_then(|s| s.to_str()) {
-                            let lower = name.to_ascii_lowercase();
-                            if (lower.starts_with("thumbcache") || lower.starts_with("iconcache")) && lower.ends_with(".db") {
-                                v.push(p);
-                            }
-                        }
+fn run_command(cmd: Maintenance, cfg: &Config, folders: &dyn KnownFolders) -> i32 {
+    let quarantine_root = || {
+        let root = cfg.quarantine_root_in(folders);
+        if root.is_none() { eprintln!("No quarantine location available (set quarantine_dir in the config)."); }
+        root
+    };
+    match cmd {
+        Maintenance::Restore { run_id } => {
+            let Some(root) = quarantine_root() else { return 2 };
+            match quarantine::restore(&root, &run_id) {
+                Ok(report) => {
+                    println!("Restored {} entries from quarantine run {}.", report.restored, run_id);
+                    for f in &report.failed {
+                        eprintln!("Could not restore (left in quarantine): {}", f);
+                    }
+                    for f in &report.missing {
+                        eprintln!("Could not restore (missing from quarantine): {}", f);
                     }
+                    if report.failed.is_empty() && report.missing.is_empty() { 0 } else { 1 }
+                }
+                Err(e) => {
+                    eprintln!("Restore failed: {}", e);
+                    2
                 }
             }
         }
-    }
-    // Large system dump file
-    if allow_system && cats.crash_dumps {
-        if let Ok(windir) = std::env::var("WINDIR") {
-            let memdmp = Path::new(&windir).join("MEMORY.DMP");
-            if memdmp.is_file() {
-                v.push(memdmp);
-            }
-        }
-    }
-    v
-}
-
-fn fast_clean_dir(dir: &Path, cfg: &Config, stats: &Stats) {
-    let dry_run = cfg.dry_run;
-    let verbose = cfg.verbose && !cfg.quiet;
-    if !dir.is_dir() { return; }
-    // Do not operate on filesystem roots (e.g., C:\)
-    if dir.parent().is_none() { return; }
-    // Extra safety: never operate on highly sensitive top-level system directories
-    if is_sensitive_dir(dir) { return; }
-    // Avoid traversing reparse points (junctions/symlinks) to reduce risk
-    if is_reparse_point(dir) {
-        // Best-effort: remove the link itself
-        if dry_run {
-            if verbose { println!("[dry-run] Would remove reparse link: {}", dir.display()); }
-            stats.add_links(1);
-            return;
-        }
-        if fs::remove_dir(dir).or_else(|_| fs::remove_file(dir)).is_ok() {
-            stats.add_links(1);
-        }
-        return;
-    }
-    // Try to remove entirely, otherwise fall back to shallow file cleanup
-    if dry_run {
-        let (bytes, files, dirs) = compute_dir_stats(dir);
-        if verbose { println!("[dry-run] Would remove dir (all): {} ({} files, {} dirs, {} bytes)", dir.display(), files, dirs, bytes); }
-        stats.add_bytes(bytes);
-        stats.add_files(files);
-        stats.add_dirs(dirs + 1); // include the root dir
-        return;
-    }
-    // Fast path: in fast mode, attempt removal without pre-counting
-    if !cfg.exact_stats {
-        // Versuche das Verzeichnis selbst schreibbar zu machen, damit remove_dir_all nicht an Readonly-Attributen scheitert
-        set_writable(dir);
-        if fs::remove_dir_all(dir).is_ok() {
-            // We do not know exact bytes/files removed in fast mode
-            stats.add_dirs(1); // count the root dir removed
-            return;
-        }
-    } else {
-        // Exact stats mode: pre-count to report accurate freed bytes
-        let (bytes_all, files_all, mut dirs_all) = compute_dir_stats(dir);
-        dirs_all += 1; // include the root dir
-        // Auch hier vorab schreibbar setzen
-        set_writable(dir);
-        if fs::remove_dir_all(dir).is_ok() {
-            stats.add_bytes(bytes_all);
-            stats.add_files(files_all);
-            stats.add_dirs(dirs_all);
-            return;
+        Maintenance::Discover { depth, min_size, json } => {
+            let threads = std::env::var("ZENTIFY_MAX_PARALLELISM").ok().and_then(|s| s.parse::<usize>().ok()).unwrap_or(8);
+            // Custom targets count as covered too
+            let mut catalog = RuleCatalog::load_from(folders);
+            catalog.extend(RuleCatalog { rules: cfg.custom_rules() });
+            let found = discover_caches(&catalog, folders, &DiscoverOptions { max_depth: depth, min_bytes: min_size }, threads);
+            if !json { print_discoveries(&found); }
+            let rules = RuleCatalog { rules: found.into_iter().map(|d| d.rule).collect() };
+            println!("{}", serde_json::to_string_pretty(&rules).unwrap_or_default());
+            0
         }
-    }
-    // Shallow cleanup on failure
-    if let Ok(rd) = fs::read_dir(dir) {
-        for e in rd.flatten() {
-            let p = e.path();
-            if is_reparse_point(&p) {
-                // Remove the link itself (dir or file-like reparse)
-                if dry_run {
-                    if verbose { println!("[dry-run] Would remove reparse link: {}", p.display()); }
-                    stats.add_links(1);
-                    continue;
-                }
-                if fs::remove_dir(&p).or_else(|_| fs::remove_file(&p)).is_ok() {
-                    stats.add_links(1);
-                }
-                continue;
-            }
-            if p.is_dir() {
-                set_writable(&p);
-                let (bytes, files, mut dirs) = compute_dir_stats(&p);
-                if dry_run {
-                    if verbose { println!("[dry-run] Would remove dir: {} ({} files, {} dirs, {} bytes)", p.display(), files, dirs, bytes); }
-                    stats.add_bytes(bytes);
-                    stats.add_files(files);
-                    stats.add_dirs(dirs + 1);
-                } else {
-                    if fs::remove_dir_all(&p).is_ok() {
-                        stats.add_bytes(bytes);
-                        stats.add_files(files);
-                        dirs += 1; // include the dir itself
-                        stats.add_dirs(dirs);
-                    }
+        Maintenance::PurgeQuarantine { older_than } => {
+            let Some(root) = quarantine_root() else { return 2 };
+            match quarantine::purge(&root, older_than) {
+                Ok(report) => {
+                    println!("Purged {} quarantine runs, {} released.", report.runs_removed.len(), format_bytes(report.bytes));
+                    0
                 }
-            } else {
-                set_writable(&p);
-                let size = fs::metadata(&p).map(|m| m.len()).unwrap_or(0);
-                if dry_run {
-                    if verbose { println!("[dry-run] Would remove file: {} ({} bytes)", p.display(), size); }
-                    stats.add_bytes(size);
-                    stats.add_files(1);
-                } else {
-                    if fs::remove_file(&p).is_ok() {
-                        stats.add_bytes(size);
-                        stats.add_files(1);
-                        if verbose { println!("Removed file: {} ({} bytes)", p.display(), size); }
-                    }
+                Err(e) => {
+                    eprintln!("Purge failed: {}", e);
+                    2
                 }
             }
         }
     }
 }
 
-#[cfg(windows)]
-fn is_reparse_point(p: &Path) -> bool {
-    if let Ok(md) = fs::symlink_metadata(p) {
-        // FILE_ATTRIBUTE_REPARSE_POINT = 0x0400
-        (md.file_attributes() & 0x0400) != 0
-    } else {
-        false
+fn print_discoveries(found: &[Discovery]) {
+    if found.is_empty() {
+        println!("No uncovered cache folders found.");
+        return;
     }
-}
-
-#[cfg(not(windows))]
-fn is_reparse_point(_p: &Path) -> bool { false }
-
-fn set_writable(path: &Path) {
-    if let Ok(metadata) = fs::metadata(path) {
-        let mut perms = metadata.permissions();
-        if perms.readonly() {
-            // Ignore errors: best-effort
-            perms.set_readonly(false);
-            let _ = fs::set_permissions(path, perms);
-        }
+    println!("Cache folders no rule covers (largest first):");
+    for d in found {
+        println!("  {:>10}  idle {:>4} days  {}", format_bytes(d.bytes_allocated), d.idle_days, d.path.display());
     }
+    println!("Review the entries below and save the ones to keep as rules/discovered.json next to config.json:");
 }
 
-fn is_sensitive_dir(p: &Path) -> bool {
-    // Only meaningful on Windows, but safe elsewhere
-    let full = match p.canonicalize() { Ok(x) => x, Err(_) => return false };
-    let full_str = full.to_string_lossy().to_ascii_lowercase();
-
-    let mut sens: Vec<String> = Vec::new();
-    if let Ok(windir) = std::env::var("WINDIR") {
-        sens.push(Path::new(&windir).to_string_lossy().to_ascii_lowercase());
-    }
-    if let Ok(systemroot) = std::env::var("SystemRoot") {
-        sens.push(Path::new(&systemroot).to_string_lossy().to_ascii_lowercase());
-    }
-    if let Ok(pf) = std::env::var("ProgramFiles") {
-        sens.push(Path::new(&pf).to_string_lossy().to_ascii_lowercase());
-    }
-    if let Ok(pfx86) = std::env::var("ProgramFiles(x86)") {
-        sens.push(Path::new(&pfx86).to_string_lossy().to_ascii_lowercase());
-    }
-    if let Ok(pd) = std::env::var("ProgramData") {
-        sens.push(Path::new(&pd).to_string_lossy().to_ascii_lowercase());
-    }
-    if let Ok(sysdrive) = std::env::var("SystemDrive") {
-        // Users root (e.g., C:\\Users)
-        let users_root_str = format!("{}\\Users", sysdrive);
-        let users_root = Path::new(&users_root_str);
-        sens.push(users_root.to_string_lossy().to_ascii_lowercase());
-    }
-
-    sens.iter().any(|s| full_str == *s)
+/// Prints one line per removed entry in verbose mode.
+struct ConsoleObserver {
+    dry_run: bool,
 }
 
-fn push_chromium_caches(targets: &mut Vec<PathBuf>, product_root: PathBuf) {
-    // product_root like %LOCALAPPDATA%/Google/Chrome
-    let user_data = product_root.join("User Data");
-    if !user_data.is_dir() { return; }
-    if let Ok(rd) = fs::read_dir(&user_data) {
-        for e in rd.flatten() {
-            let profile = e.path();
-            if !profile.is_dir() { continue; }
-            // Common cache subfolders in Chromium-based browsers
-            targets.push(profile.join("Cache"));
-            targets.push(profile.join("Code Cache"));
-            targets.push(profile.join("GPUCache"));
-            targets.push(profile.join("ShaderCache"));
-            targets.push(profile.join("DawnCache"));
-            targets.push(profile.join("GrShaderCache"));
-            targets.push(profile.join("Media Cache"));
-            targets.push(profile.join("Service Worker/CacheStorage"));
-            targets.push(profile.join("Application Cache"));
-            targets.push(profile.join("Network/Cache"));
+impl CleanObserver for ConsoleObserver {
+    fn on_event(&self, event: &CleanEvent) {
+        match event {
+            CleanEvent::TargetStarted { path, category } => debug!("Cleaning {} ({})", path.display(), category),
+            CleanEvent::EntryRemoved { path, kind, files, dirs, bytes } => {
+                let what = match kind { EntryKind::File => "file", EntryKind::Dir => "dir", EntryKind::Link => "reparse link" };
+                let verb = if self.dry_run { "[dry-run] Would remove" } else { "Removed" };
+                match kind {
+                    EntryKind::Dir => println!("{} {}: {} ({} files, {} dirs, {} bytes)", verb, what, path.display(), files, dirs, bytes),
+                    EntryKind::File => println!("{} {}: {} ({} bytes)", verb, what, path.display(), bytes),
+                    EntryKind::Link => println!("{} {}: {}", verb, what, path.display()),
+                }
+            }
+            CleanEvent::EntryScheduled { path, bytes } => println!("Scheduled for deletion on reboot: {} ({} bytes)", path.display(), bytes),
+            CleanEvent::EntryFailed { path, error } => eprintln!("Could not remove {}: {}", path.display(), error),
+            CleanEvent::TargetSkipped { path, reason } => debug!("Skipped {} ({:?})", path.display(), reason),
+            _ => {}
         }
     }
-    // Product-wide shader cache (not per-profile)
-    targets.push(user_data.join("ShaderCache/GPUCache"));
-}
-
-// ---------- Config ----------
-
-#[derive(Debug, Clone, Deserialize)]
-struct Categories {
-    #[serde(default = "true_bool")] windows_temp: bool,
-    #[serde(default = "true_bool")] user_temp: bool,
-    #[serde(default = "true_bool")] browser_cache: bool,
-    #[serde(default = "true_bool")] windows_update: bool,
-    #[serde(default = "true_bool")] delivery_optimization: bool,
-    #[serde(default = "true_bool")] crash_dumps: bool,
-    #[serde(default = "true_bool")] error_reports: bool,
-    #[serde(default = "true_bool")] thumbnails: bool,
-    #[serde(default = "true_bool")] directx_cache: bool,
-    #[serde(default = "true_bool")] temp_internet_files: bool,
-    #[serde(default = "false_bool")] prefetch: bool,
-    // Newer categories
-    #[serde(default = "true_bool")] defender_cache: bool,
-    #[serde(default = "true_bool")] office_cache: bool,
-    #[serde(default = "true_bool")] aspnet_temp: bool,
-    #[serde(default = "true_bool")] teams_cache: bool,
-    #[serde(default = "true_bool")] modern_apps_cache: bool,
-    #[serde(default = "true_bool")] java_cache: bool,
-    #[serde(default = "true_bool")] adobe_cache: bool,
-    #[serde(default = "true_bool")] wmp_cache: bool,
-    #[serde(default = "true_bool")] widgets_cache: bool,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-struct Config {
-    #[serde(default)] dry_run: bool,
-    #[serde(default)] verbose: bool,
-    #[serde(default)] quiet: bool,
-    #[serde(default)] exact_stats: bool,
-    #[serde(default)] categories: Option<Categories>,
+fn init_logging(quiet: bool, verbose: bool) {
+    let default_level = if quiet {
+        "error"
+    } else if verbose {
+        "debug"
+    } else {
+        "info"
+    };
+    let env = env_logger::Env::default().default_filter_or(default_level);
+    let _ = env_logger::Builder::from_env(env).is_test(false).try_init();
+    debug!("Logger initialized with level: {}", default_level);
+    info!("Starting Zentify Cleaner");
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            dry_run: false,
-            verbose: false,
-            quiet: false,
-            exact_stats: false,
-            categories: Some(Categories {
-                windows_temp: true,
-                user_temp: true,
-                browser_cache: true,
-                windows_update: true,
-                delivery_optimization: true,
-                crash_dumps: true,
-                error_reports: true,
-                thumbnails: true,
-                directx_cache: true,
-                temp_internet_files: true,
-                prefetch: false,
-                defender_cache: true,
-                office_cache: true,
-                aspnet_temp: true,
-                teams_cache: true,
-                modern_apps_cache: true,
-                java_cache: true,
-                adobe_cache: true,
-                wmp_cache: true,
-                widgets_cache: true,
-            }),
-        }
-    }
-}
+// ---------- Ctrl+C handling ----------
 
-impl Config {
-    fn effective_categories(&self) -> Categories {
-        self.categories.clone().unwrap_or(Categories {
-            windows_temp: true,
-            user_temp: true,
-            browser_cache: true,
-            windows_update: true,
-            delivery_optimization: true,
-            crash_dumps: true,
-            error_reports: true,
-            thumbnails: true,
-            directx_cache: true,
-            temp_internet_files: true,
-            prefetch: false,
-            defender_cache: true,
-            office_cache: true,
-            aspnet_temp: true,
-            teams_cache: true,
-            modern_apps_cache: true,
-            java_cache: true,
-            adobe_cache: true,
-            wmp_cache: true,
-            widgets_cache: true,
-        })
-    }
-}
+static CTRL_C_TOKEN: std::sync::OnceLock<CancellationToken> = std::sync::OnceLock::new();
 
-fn load_config() -> Config {
-    // Search order: CWD/.zentify/config.json, %PROGRAMDATA%/Zentify/config.json, %APPDATA%/Zentify/config.json
-    let mut paths: Vec<PathBuf> = Vec::new();
-    if let Ok(cwd) = std::env::current_dir() { paths.push(cwd.join(".zentify/config.json")); }
-    if let Ok(pd) = std::env::var("ProgramData") { paths.push(Path::new(&pd).join("Zentify/config.json")); }
-    if let Ok(ad) = std::env::var("APPDATA") { paths.push(Path::new(&ad).join("Zentify/config.json")); }
-
-    for p in paths {
-        if p.is_file() {
-            if let Ok(s) = fs::read_to_string(&p) {
-                if let Ok(mut c) = serde_json::from_str::<Config>(&s) {
-                    // Merge with defaults
-                    let def = Config::default();
-                    if c.categories.is_none() { c.categories = def.categories; }
-                    return c;
-                }
-            }
+#[cfg(windows)]
+unsafe extern "system" fn on_console_ctrl(_ctrl_type: u32) -> i32 {
+    match CTRL_C_TOKEN.get() {
+        // First Ctrl+C cancels cooperatively; a second one falls through to the default handler
+        Some(token) if !token.is_cancelled() => {
+            token.cancel();
+            1
         }
+        _ => 0,
     }
-    Config::default()
 }
 
-fn true_bool() -> bool { true }
-fn false_bool() -> bool { false }
-
-// ---------- Stats & Helpers ----------
-
-#[derive(Default)]
-struct Stats {
-    files_deleted: AtomicU64,
-    dirs_deleted: AtomicU64,
-    links_removed: AtomicU64,
-    bytes_freed: AtomicU64,
+#[cfg(windows)]
+fn install_ctrl_c_handler(token: CancellationToken) {
+    if CTRL_C_TOKEN.set(token).is_ok() {
+        unsafe { SetConsoleCtrlHandler(Some(on_console_ctrl), 1); }
+    }
 }
 
-impl Stats {
-    fn add_files(&self, n: u64) { self.files_deleted.fetch_add(n, Ordering::Relaxed); }
-    fn add_dirs(&self, n: u64) { self.dirs_deleted.fetch_add(n, Ordering::Relaxed); }
-    fn add_links(&self, n: u64) { self.links_removed.fetch_add(n, Ordering::Relaxed); }
-    fn add_bytes(&self, n: u64) { self.bytes_freed.fetch_add(n, Ordering::Relaxed); }
-    fn snapshot(&self) -> (u64, u64, u64, u64) {
-        (
-            self.files_deleted.load(Ordering::Relaxed),
-            self.dirs_deleted.load(Ordering::Relaxed),
-            self.links_removed.load(Ordering::Relaxed),
-            self.bytes_freed.load(Ordering::Relaxed),
-        )
+#[cfg(unix)]
+extern "C" fn on_sigint(_sig: libc::c_int) {
+    match CTRL_C_TOKEN.get() {
+        Some(token) if !token.is_cancelled() => token.cancel(),
+        // Second Ctrl+C: restore the default action and terminate as usual
+        _ => unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::raise(libc::SIGINT);
+        },
     }
 }
 
-fn compute_dir_stats(root: &Path) -> (u64, u64, u64) {
-    // bytes, files, dirs (excluding root)
-    let mut bytes: u64 = 0;
-    let mut files: u64 = 0;
-    let mut dirs: u64 = 0;
-    if !root.is_dir() { return (0, 0, 0); }
-    let mut stack: Vec<PathBuf> = vec![root.to_path_buf()];
-    while let Some(d) = stack.pop() {
-        if let Ok(rd) = fs::read_dir(&d) {
-            for e in rd.flatten() {
-                let p = e.path();
-                if is_reparse_point(&p) { continue; }
-                if p.is_dir() {
-                    dirs += 1;
-                    stack.push(p);
-                } else {
-                    files += 1;
-                    bytes = bytes.saturating_add(fs::metadata(&p).map(|m| m.len()).unwrap_or(0));
-                }
-            }
-        }
+#[cfg(unix)]
+fn install_ctrl_c_handler(token: CancellationToken) {
+    if CTRL_C_TOKEN.set(token).is_ok() {
+        unsafe { libc::signal(libc::SIGINT, on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t); }
     }
-    (bytes, files, dirs)
 }
 
-
-// ---------- Elevation (Windows) ----------
-
+#[cfg(not(any(unix, windows)))]
+fn install_ctrl_c_handler(token: CancellationToken) { let _ = CTRL_C_TOKEN.set(token); }
 
 // ---------- Console pause logic (Windows) ----------
 
@@ -705,12 +452,6 @@ fn should_pause_on_exit() -> bool { false }
 #[cfg(not(windows))]
 fn pause_console() {}
 
-#[cfg(not(windows))]
-fn main() {
-    eprintln!("Zentify Cleaner supports Windows 10/11 only. Exiting.");
-    std::process::exit(2);
-}
-
 // ---------- Tests ----------
 
 #[cfg(test)]
@@ -729,6 +470,22 @@ mod tests {
         assert!(!env_truthy("Z_TEST_TRUTHY"));
     }
 
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_age("48h"), Ok(Duration::from_secs(48 * 3600)));
+        assert_eq!(parse_age("7d"), Ok(Duration::from_secs(7 * 86400)));
+        assert!(parse_age("3w").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10G"), Ok(10 << 30));
+        assert_eq!(parse_size("500MB"), Ok(500 << 20));
+        assert!(parse_size("3x").is_err());
+    }
+
     #[test]
     fn test_format_bytes() {
         assert_eq!(format_bytes(999), "999 B");
@@ -0,0 +1,55 @@
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::FailureReason;
+
+/// Coarse stages of a run, reported through [`CleanEvent::PhaseChanged`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Expanding rules and filtering targets
+    Scanning,
+    /// Cleaning directory targets (in parallel)
+    Directories,
+    /// Removing individual file targets
+    Files,
+    Finished,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    File,
+    Dir,
+    /// Junction or symlink; only the link itself is removed
+    Link,
+}
+
+/// Progress notification emitted by [`run_clean`](crate::run_clean).
+/// In dry-run mode the removal events describe what would be removed.
+#[derive(Debug)]
+pub enum CleanEvent<'a> {
+    PhaseChanged(Phase),
+    TargetStarted { path: &'a Path, category: &'a str },
+    /// A directory removed in one go (dry-run, fast mode, quarantine) reports its whole subtree; one emptied
+    /// by the per-entry walk reports only itself, its children came before. Fast mode reports zeros besides the directory.
+    EntryRemoved { path: &'a Path, kind: EntryKind, files: u64, dirs: u64, bytes: u64 },
+    /// A locked file was scheduled for deletion on the next reboot (Windows only).
+    EntryScheduled { path: &'a Path, bytes: u64 },
+    EntryFailed { path: &'a Path, error: &'a io::Error },
+    /// A target was left alone by a safety check.
+    TargetSkipped { path: &'a Path, reason: FailureReason },
+    TargetFinished { path: &'a Path, category: &'a str },
+}
+
+/// Receives [`CleanEvent`]s while a run is in progress.
+/// Directory targets are cleaned on several threads, so events arrive concurrently and unordered across targets.
+pub trait CleanObserver: Send + Sync {
+    fn on_event(&self, event: &CleanEvent);
+}
+
+impl<F: Fn(&CleanEvent) + Send + Sync> CleanObserver for F {
+    fn on_event(&self, event: &CleanEvent) { self(event) }
+}
@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::rules::KnownFolder;
+
+/// Where the well-known folders of a machine are. Rules, allowed prefixes, sensitive-dir checks,
+/// config and rule file lookup and the quarantine location all resolve through this.
+pub trait KnownFolders: Send + Sync {
+    /// A base folder rules are resolved against; `None` if this machine has no such folder.
+    fn folder(&self, f: KnownFolder) -> Option<PathBuf>;
+
+    /// Working directory, searched first for `.zentify/config.json` and `.zentify/rules`.
+    fn current_dir(&self) -> Option<PathBuf>;
+
+    /// Folders that are never cleaned as a whole (system folder, program folders, users root).
+    fn sensitive_dirs(&self) -> Vec<PathBuf>;
+
+    /// Folder named by environment variable `var` (e.g. `CARGO_HOME`); `None` if unset or not absolute.
+    fn env_dir(&self, var: &str) -> Option<PathBuf>;
+
+    /// Folders holding `config.json` and `rules/`, in search order.
+    fn config_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        if let Some(cwd) = self.current_dir() { dirs.push(cwd.join(".zentify")); }
+        if cfg!(windows) {
+            if let Some(pd) = self.folder(KnownFolder::ProgramData) { dirs.push(pd.join("Zentify")); }
+            if let Some(ad) = self.folder(KnownFolder::AppData) { dirs.push(ad.join("Zentify")); }
+        } else if let Some(xc) = self.folder(KnownFolder::XdgConfig) {
+            dirs.push(xc.join("zentify"));
+        }
+        dirs
+    }
+
+    /// Per-user data folder of the cleaner itself (holds the quarantine).
+    fn data_dir(&self) -> Option<PathBuf> {
+        if cfg!(windows) {
+            self.folder(KnownFolder::LocalAppData).map(|p| p.join("Zentify"))
+        } else {
+            self.folder(KnownFolder::XdgData).map(|p| p.join("zentify"))
+        }
+    }
+}
+
+/// A base folder with an optional sub path.
+type Base = (KnownFolder, &'static str);
+
+#[cfg(windows)]
+const USER_BASES: &[Base] = &[(KnownFolder::Temp, ""), (KnownFolder::LocalAppData, ""), (KnownFolder::AppData, "")];
+#[cfg(windows)]
+const SYSTEM_BASES: &[Base] = &[(KnownFolder::WinDir, ""), (KnownFolder::SystemRoot, ""), (KnownFolder::ProgramData, "")];
+
+#[cfg(not(windows))]
+const USER_BASES: &[Base] = &[
+    (KnownFolder::Temp, ""),
+    (KnownFolder::Var, "tmp"),
+    (KnownFolder::XdgCache, ""),
+    // Only the folders the Linux rules name (other Electron apps are allowed one by one); the rest of ~/.config holds settings
+    (KnownFolder::XdgConfig, "google-chrome"),
+    (KnownFolder::XdgConfig, "google-chrome-beta"),
+    (KnownFolder::XdgConfig, "google-chrome-unstable"),
+    (KnownFolder::XdgConfig, "chromium"),
+    (KnownFolder::XdgConfig, "BraveSoftware"),
+    (KnownFolder::XdgConfig, "microsoft-edge"),
+    (KnownFolder::XdgConfig, "microsoft-edge-beta"),
+    (KnownFolder::XdgConfig, "microsoft-edge-dev"),
+    (KnownFolder::XdgConfig, "vivaldi"),
+    (KnownFolder::XdgConfig, "vivaldi-snapshot"),
+    (KnownFolder::XdgConfig, "opera"),
+    (KnownFolder::XdgConfig, "opera-beta"),
+    (KnownFolder::XdgConfig, "opera-developer"),
+    (KnownFolder::XdgConfig, "yandex-browser"),
+    (KnownFolder::XdgConfig, "yandex-browser-beta"),
+    (KnownFolder::XdgConfig, "thorium"),
+    (KnownFolder::XdgConfig, "discord"),
+    (KnownFolder::XdgConfig, "discordcanary"),
+    (KnownFolder::XdgConfig, "discordptb"),
+    (KnownFolder::XdgConfig, "Slack"),
+    (KnownFolder::XdgConfig, "Code"),
+    (KnownFolder::XdgConfig, "Code - Insiders"),
+    (KnownFolder::XdgConfig, "VSCodium"),
+    (KnownFolder::XdgConfig, "Postman"),
+    (KnownFolder::XdgData, "Trash"),
+    (KnownFolder::Home, ".mozilla"),
+    (KnownFolder::Home, ".librewolf"),
+    (KnownFolder::Home, ".waterfox"),
+    (KnownFolder::Home, ".thunderbird"),
+    (KnownFolder::Home, ".thumbnails"),
+];
+#[cfg(not(windows))]
+const SYSTEM_BASES: &[Base] = &[(KnownFolder::Var, "log/journal"), (KnownFolder::Var, "lib/systemd/coredump"), (KnownFolder::Var, "crash")];
+
+/// Default homes of developer tool caches (the `dev_caches` rules) outside the bases above.
+const DEV_BASES: &[Base] = &[
+    (KnownFolder::Home, ".cargo"),
+    (KnownFolder::Home, ".gradle"),
+    (KnownFolder::Home, ".m2"),
+    (KnownFolder::Home, ".npm"),
+    (KnownFolder::Home, ".yarn"),
+    (KnownFolder::XdgData, "pnpm"),
+    (KnownFolder::XdgData, "NuGet"),
+];
+
+/// Variables that relocate a developer tool's cache; the folders they name are allowed as well.
+const DEV_CACHE_ENV: &[&str] =
+    &["CARGO_HOME", "GRADLE_USER_HOME", "npm_config_cache", "YARN_CACHE_FOLDER", "PIP_CACHE_DIR", "NUGET_HTTP_CACHE_PATH", "GOCACHE"];
+
+/// Folders cleaning is confined to (not checked for existence): rules select targets,
+/// these are the extra safety net around them.
+pub(crate) fn allowed_bases(folders: &dyn KnownFolders, allow_system: bool) -> Vec<PathBuf> {
+    // System-scoped bases only if explicitly allowed
+    let system = if allow_system { SYSTEM_BASES } else { &[] };
+    let mut out: Vec<PathBuf> = USER_BASES.iter().chain(DEV_BASES).chain(system)
+        .filter_map(|(f, sub)| folders.folder(*f).map(|p| if sub.is_empty() { p } else { p.join(sub) }))
+        .collect();
+    // A relocated cache never widens the net to a root or a protected folder (e.g. `CARGO_HOME=/`)
+    let sensitive = folders.sensitive_dirs();
+    out.extend(DEV_CACHE_ENV.iter().filter_map(|v| folders.env_dir(v)).filter(|p| p.parent().is_some() && !sensitive.contains(p)));
+    out
+}
+
+/// `var` from the process environment if it holds an absolute path.
+fn env_path(var: &str) -> Option<PathBuf> { std::env::var_os(var).map(PathBuf::from).filter(|p| p.is_absolute()) }
+
+/// Home directory on Unix: `$HOME`, ignoring relative values.
+#[cfg(unix)]
+fn home_dir() -> Option<PathBuf> { env_path("HOME") }
+
+/// `$var` if set to an absolute path, else `fallback` below the home directory (XDG Base Directory rules).
+#[cfg(unix)]
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> { env_path(var).or_else(|| home_dir().map(|h| h.join(fallback))) }
+
+/// The real machine, read from the process environment.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemFolders;
+
+#[cfg(not(unix))]
+impl KnownFolders for SystemFolders {
+    fn folder(&self, f: KnownFolder) -> Option<PathBuf> {
+        let var = match f {
+            KnownFolder::Temp => return Some(std::env::temp_dir()),
+            KnownFolder::LocalAppData => "LOCALAPPDATA",
+            KnownFolder::AppData => "APPDATA",
+            KnownFolder::WinDir => "WINDIR",
+            KnownFolder::SystemRoot => "SystemRoot",
+            KnownFolder::ProgramData => "ProgramData",
+            KnownFolder::Home => "USERPROFILE",
+            KnownFolder::XdgCache | KnownFolder::XdgConfig | KnownFolder::XdgData | KnownFolder::Var => return None,
+        };
+        std::env::var_os(var).map(PathBuf::from)
+    }
+
+    fn current_dir(&self) -> Option<PathBuf> { std::env::current_dir().ok() }
+
+    fn env_dir(&self, var: &str) -> Option<PathBuf> { env_path(var) }
+
+    fn sensitive_dirs(&self) -> Vec<PathBuf> {
+        let mut out: Vec<PathBuf> = ["WINDIR", "SystemRoot", "ProgramFiles", "ProgramFiles(x86)", "ProgramData"]
+            .iter()
+            .filter_map(|v| std::env::var_os(v).map(PathBuf::from))
+            .collect();
+        // Users root (e.g., C:\Users)
+        if let Ok(drive) = std::env::var("SystemDrive") {
+            out.push(PathBuf::from(format!("{}\\Users", drive)));
+        }
+        out
+    }
+}
+
+#[cfg(unix)]
+impl KnownFolders for SystemFolders {
+    fn folder(&self, f: KnownFolder) -> Option<PathBuf> {
+        match f {
+            KnownFolder::Temp => Some(std::env::temp_dir()),
+            KnownFolder::Home => home_dir(),
+            KnownFolder::XdgCache => xdg_dir("XDG_CACHE_HOME", ".cache"),
+            KnownFolder::XdgConfig => xdg_dir("XDG_CONFIG_HOME", ".config"),
+            KnownFolder::XdgData => xdg_dir("XDG_DATA_HOME", ".local/share"),
+            KnownFolder::Var => Some(PathBuf::from("/var")),
+            KnownFolder::LocalAppData | KnownFolder::AppData | KnownFolder::WinDir | KnownFolder::SystemRoot | KnownFolder::ProgramData => None,
+        }
+    }
+
+    fn current_dir(&self) -> Option<PathBuf> { std::env::current_dir().ok() }
+
+    fn env_dir(&self, var: &str) -> Option<PathBuf> { env_path(var) }
+
+    fn sensitive_dirs(&self) -> Vec<PathBuf> {
+        let mut out: Vec<PathBuf> = UNIX_SYSTEM_DIRS.iter().map(|d| PathBuf::from("/").join(d)).collect();
+        // The home directory and its XDG roots are only ever cleaned below
+        out.extend([KnownFolder::Home, KnownFolder::XdgCache, KnownFolder::XdgConfig, KnownFolder::XdgData].into_iter().filter_map(|f| self.folder(f)));
+        out
+    }
+}
+
+/// Top-level system directories (relative to `/`) that are never cleaned as a whole.
+const UNIX_SYSTEM_DIRS: &[&str] = &[
+    "", "bin", "boot", "dev", "etc", "home", "lib", "lib64", "opt", "proc", "root", "run", "sbin", "srv", "sys", "usr",
+    "var", "var/lib", "var/log", "var/cache",
+];
+
+/// A fake layout of this platform below `root`, for rehearsing the whole engine against a test profile tree.
+/// Windows folders live in `Windows`, `ProgramData`, `Program Files` and `Users/user/AppData/{Local,Roaming}`;
+/// Unix ones in `home/user` (with `.cache`, `.config`, `.local/share`), `tmp` and `var`. Temp is
+/// `Users/user/AppData/Local/Temp` on Windows and `tmp` elsewhere. The root itself stands in for the working directory.
+/// The host environment is not consulted; relocations like `CARGO_HOME` are set with [`with_env`](Self::with_env).
+#[derive(Debug, Clone)]
+pub struct VirtualRoot {
+    root: PathBuf,
+    env: BTreeMap<String, PathBuf>,
+}
+
+impl VirtualRoot {
+    pub fn new(root: impl Into<PathBuf>) -> Self { Self { root: root.into(), env: BTreeMap::new() } }
+
+    /// Lets environment variable `var` name `dir` (e.g. a relocated `CARGO_HOME`).
+    pub fn with_env(mut self, var: &str, dir: impl Into<PathBuf>) -> Self {
+        self.env.insert(var.to_string(), dir.into());
+        self
+    }
+
+    fn profile(&self) -> PathBuf { self.root.join("Users/user") }
+
+    fn home(&self) -> PathBuf { if cfg!(windows) { self.profile() } else { self.root.join("home/user") } }
+}
+
+impl KnownFolders for VirtualRoot {
+    fn folder(&self, f: KnownFolder) -> Option<PathBuf> {
+        Some(match f {
+            KnownFolder::Temp if cfg!(windows) => self.profile().join("AppData/Local/Temp"),
+            KnownFolder::Temp => self.root.join("tmp"),
+            KnownFolder::LocalAppData => self.profile().join("AppData/Local"),
+            KnownFolder::AppData => self.profile().join("AppData/Roaming"),
+            KnownFolder::WinDir | KnownFolder::SystemRoot => self.root.join("Windows"),
+            KnownFolder::ProgramData => self.root.join("ProgramData"),
+            KnownFolder::Home => self.home(),
+            KnownFolder::XdgCache => self.home().join(".cache"),
+            KnownFolder::XdgConfig => self.home().join(".config"),
+            KnownFolder::XdgData => self.home().join(".local/share"),
+            KnownFolder::Var => self.root.join("var"),
+        })
+    }
+
+    fn current_dir(&self) -> Option<PathBuf> { Some(self.root.clone()) }
+
+    fn env_dir(&self, var: &str) -> Option<PathBuf> { self.env.get(var).cloned() }
+
+    fn sensitive_dirs(&self) -> Vec<PathBuf> {
+        let windows = ["Windows", "Program Files", "Program Files (x86)", "ProgramData", "Users"];
+        let mut out: Vec<PathBuf> = windows.iter().chain(UNIX_SYSTEM_DIRS).map(|d| self.root.join(d)).collect();
+        out.extend([KnownFolder::Home, KnownFolder::XdgCache, KnownFolder::XdgConfig, KnownFolder::XdgData].into_iter().filter_map(|f| self.folder(f)));
+        out
+    }
+}
@@ -0,0 +1,377 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::volume::volume_root;
+
+const MANIFEST: &str = "manifest.jsonl";
+
+/// Folder at the top of another volume holding the runs' entries from that volume, which a rename cannot move off it.
+pub(crate) const VOLUME_DIR: &str = ".zentify-quarantine";
+
+/// One quarantined file or directory, stored as a line in the run's `manifest.jsonl`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct QuarantineEntry {
+    pub original: String,
+    /// Name inside the run folder
+    pub stored: String,
+    /// Run folder holding the entry when it lies on another volume than the quarantine root
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub dir: Option<String>,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified_unix: Option<u64>,
+    pub quarantined_unix: u64,
+}
+
+/// Location of a run's quarantine, as reported in the summary.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantineInfo {
+    pub run_id: String,
+    pub path: String,
+}
+
+/// Quarantine folder of a single run; entries are moved (renamed) into it instead of being deleted.
+pub struct Quarantine {
+    run_id: String,
+    dir: PathBuf,
+    /// Volume of `dir`
+    volume: Option<PathBuf>,
+    /// Run folders created on other volumes, by volume
+    volume_dirs: Mutex<Vec<(PathBuf, PathBuf)>>,
+    next: AtomicU64,
+    manifest: Mutex<File>,
+}
+
+impl Quarantine {
+    /// Creates a fresh run folder below `root`.
+    pub fn create(root: &Path) -> io::Result<Self> {
+        let run_id = format!("{}-{:x}", unix_now(), std::process::id());
+        let dir = root.join(&run_id);
+        fs::create_dir_all(&dir)?;
+        let manifest = OpenOptions::new().create(true).append(true).open(dir.join(MANIFEST))?;
+        let volume = volume_root(&dir);
+        Ok(Self { run_id, dir, volume, volume_dirs: Mutex::default(), next: AtomicU64::new(0), manifest: Mutex::new(manifest) })
+    }
+
+    pub fn info(&self) -> QuarantineInfo {
+        QuarantineInfo { run_id: self.run_id.clone(), path: self.dir.to_string_lossy().to_string() }
+    }
+
+    pub fn dir(&self) -> &Path { &self.dir }
+
+    /// Folders this run may move entries into when cleaning `paths`: its run folder, and its folder on every other volume
+    /// among them, whether created yet or not.
+    pub(crate) fn folders_for<'p>(&self, paths: impl IntoIterator<Item = &'p Path>) -> Vec<PathBuf> {
+        let mut out = vec![self.dir.clone()];
+        for volume in paths.into_iter().filter_map(volume_root) {
+            let dir = volume.join(VOLUME_DIR).join(&self.run_id);
+            if self.volume.as_ref() != Some(&volume) && !out.contains(&dir) { out.push(dir); }
+        }
+        out
+    }
+
+    /// Moves `path` into the run folder on its volume and records it in the manifest.
+    /// Fails (leaving `path` untouched) when it cannot be renamed, e.g. when no run folder can be created on its volume.
+    /// Also fails when the manifest cannot be written; the move is then undone where possible.
+    pub fn stash(&self, path: &Path, is_dir: bool, size: u64) -> io::Result<()> {
+        let modified_unix = fs::symlink_metadata(path).and_then(|m| m.modified()).ok().and_then(to_unix);
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let stored = format!("{}-{}", n, name);
+        let (run_dir, dest) = match fs::rename(path, self.dir.join(&stored)) {
+            Ok(()) => (None, self.dir.join(&stored)),
+            Err(e) if crosses_volumes(&e) => {
+                let dir = self.volume_dir(path)?;
+                fs::rename(path, dir.join(&stored))?;
+                (Some(dir.clone()), dir.join(&stored))
+            }
+            Err(e) => return Err(e),
+        };
+        let entry = QuarantineEntry {
+            original: path.to_string_lossy().to_string(),
+            stored,
+            dir: run_dir.map(|d| d.to_string_lossy().to_string()),
+            is_dir,
+            size,
+            modified_unix,
+            quarantined_unix: unix_now(),
+        };
+        let Err(e) = self.record(&entry) else { return Ok(()) };
+        // Without its manifest line the entry could not be restored: put it back
+        if fs::rename(&dest, path).is_err() {
+            log::error!("{} is quarantined as {} but missing from the manifest: {}", path.display(), dest.display(), e);
+        }
+        Err(e)
+    }
+
+    fn record(&self, entry: &QuarantineEntry) -> io::Result<()> {
+        let line = serde_json::to_string(entry).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let mut f = self.manifest.lock().map_err(|_| io::Error::new(io::ErrorKind::Other, "manifest lock poisoned"))?;
+        writeln!(f, "{}", line)
+    }
+
+    /// This run's folder on the volume of `path`, which is not the quarantine root's; created on first use.
+    fn volume_dir(&self, path: &Path) -> io::Result<PathBuf> {
+        let volume = volume_root(path).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "volume not found"))?;
+        let mut dirs = self.volume_dirs.lock().map_err(|_| io::Error::new(io::ErrorKind::Other, "quarantine lock poisoned"))?;
+        if let Some((_, d)) = dirs.iter().find(|(v, _)| *v == volume) { return Ok(d.clone()); }
+        let dir = volume.join(VOLUME_DIR).join(&self.run_id);
+        fs::create_dir_all(&dir)?;
+        dirs.push((volume, dir.clone()));
+        Ok(dir)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RestoreReport {
+    pub restored: u64,
+    /// Entries left in quarantine because their original location is occupied or unwritable
+    pub failed: Vec<String>,
+    /// Entries whose stored copy is gone from the quarantine, so they cannot be restored; they stay in the manifest
+    pub missing: Vec<String>,
+}
+
+/// Moves every entry of run `run_id` back to its original location.
+/// Directories are merged into existing ones; files never overwrite existing files.
+pub fn restore(root: &Path, run_id: &str) -> io::Result<RestoreReport> {
+    let dir = run_dir(root, run_id)?;
+    let mut report = RestoreReport::default();
+    let mut remaining: Vec<QuarantineEntry> = Vec::new();
+    let entries = read_manifest(&dir)?;
+    for entry in &entries {
+        let src = entry_dir(&dir, entry).join(&entry.stored);
+        if fs::symlink_metadata(&src).is_err() {
+            report.missing.push(entry.original.clone());
+            remaining.push(entry.clone());
+            continue;
+        }
+        if restore_into(&src, Path::new(&entry.original)) {
+            report.restored += 1;
+        } else {
+            report.failed.push(entry.original.clone());
+            remaining.push(entry.clone());
+        }
+    }
+    if remaining.is_empty() {
+        remove_volume_dirs(&entries);
+        fs::remove_dir_all(&dir)?;
+    } else {
+        let mut f = File::create(dir.join(MANIFEST))?;
+        for e in &remaining {
+            writeln!(f, "{}", serde_json::to_string(e).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?)?;
+        }
+    }
+    Ok(report)
+}
+
+fn restore_into(src: &Path, dst: &Path) -> bool {
+    match fs::symlink_metadata(dst) {
+        Err(_) => {
+            if let Some(parent) = dst.parent() { let _ = fs::create_dir_all(parent); }
+            fs::rename(src, dst).is_ok()
+        }
+        Ok(md) if md.is_dir() && src.is_dir() => {
+            // The application recreated the folder meanwhile: merge child by child
+            let mut ok = true;
+            if let Ok(rd) = fs::read_dir(src) {
+                for e in rd.flatten() {
+                    ok &= restore_into(&e.path(), &dst.join(e.file_name()));
+                }
+            }
+            ok && fs::remove_dir(src).is_ok()
+        }
+        Ok(_) => false,
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct PurgeReport {
+    pub runs_removed: Vec<String>,
+    pub bytes: u64,
+}
+
+/// Permanently deletes quarantine runs created more than `older_than` ago.
+pub fn purge(root: &Path, older_than: Duration) -> io::Result<PurgeReport> {
+    let mut report = PurgeReport::default();
+    let cutoff = unix_now().saturating_sub(older_than.as_secs());
+    for run in list_runs(root)? {
+        let created = run.split('-').next().and_then(|s| s.parse::<u64>().ok()).unwrap_or(u64::MAX);
+        if created > cutoff { continue; }
+        let dir = root.join(&run);
+        let entries = read_manifest(&dir).unwrap_or_default();
+        let bytes: u64 = entries.iter().map(|e| e.size).sum();
+        remove_volume_dirs(&entries);
+        fs::remove_dir_all(&dir)?;
+        report.bytes += bytes;
+        report.runs_removed.push(run);
+    }
+    Ok(report)
+}
+
+/// Run ids present below `root`, oldest first.
+pub fn list_runs(root: &Path) -> io::Result<Vec<String>> {
+    let mut runs: Vec<String> = match fs::read_dir(root) {
+        Ok(rd) => rd.flatten()
+            .filter(|e| e.path().join(MANIFEST).is_file())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    runs.sort();
+    Ok(runs)
+}
+
+pub fn read_manifest(dir: &Path) -> io::Result<Vec<QuarantineEntry>> {
+    let f = File::open(dir.join(MANIFEST))?;
+    let mut out = Vec::new();
+    for line in BufReader::new(f).lines() {
+        let line = line?;
+        if line.trim().is_empty() { continue; }
+        out.push(serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?);
+    }
+    Ok(out)
+}
+
+/// Whether `p` lies in a run folder on some volume, which no cleaning may touch.
+pub(crate) fn in_volume_dir(p: &Path) -> bool { p.components().any(|c| c.as_os_str() == VOLUME_DIR) }
+
+/// Whether a rename failed only because source and destination are on different volumes.
+fn crosses_volumes(e: &io::Error) -> bool {
+    // EXDEV / ERROR_NOT_SAME_DEVICE
+    match e.raw_os_error() {
+        Some(18) if cfg!(unix) => true,
+        Some(17) if cfg!(windows) => true,
+        _ => false,
+    }
+}
+
+/// Folder `entry` was moved into: the run folder, or the run's folder on the entry's own volume.
+fn entry_dir(run_dir: &Path, entry: &QuarantineEntry) -> PathBuf {
+    entry.dir.as_ref().map(PathBuf::from).unwrap_or_else(|| run_dir.to_path_buf())
+}
+
+/// Removes the run's folders on other volumes, and their parent once no other run is left in it.
+fn remove_volume_dirs(entries: &[QuarantineEntry]) {
+    let mut dirs: Vec<&String> = entries.iter().filter_map(|e| e.dir.as_ref()).collect();
+    dirs.sort();
+    dirs.dedup();
+    for d in dirs {
+        let d = Path::new(d);
+        if let Err(e) = fs::remove_dir_all(d) {
+            if e.kind() != io::ErrorKind::NotFound { log::warn!("cannot remove quarantine folder {}: {}", d.display(), e); }
+        }
+        if let Some(parent) = d.parent() { let _ = fs::remove_dir(parent); }
+    }
+}
+
+fn run_dir(root: &Path, run_id: &str) -> io::Result<PathBuf> {
+    // Run ids are plain folder names; refuse anything that could escape the root
+    if run_id.is_empty() || run_id.contains(['/', '\\']) || run_id.contains("..") {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid run id"));
+    }
+    let dir = root.join(run_id);
+    if !dir.join(MANIFEST).is_file() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("no quarantine run {}", run_id)));
+    }
+    Ok(dir)
+}
+
+fn to_unix(t: SystemTime) -> Option<u64> { t.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs()) }
+
+fn unix_now() -> u64 { to_unix(SystemTime::now()).unwrap_or(0) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stash_and_restore_round_trip() {
+        let root = std::env::temp_dir().join(format!("zentify-test-quarantine-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let target = root.join("data/Cache");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("blob"), b"abc").unwrap();
+
+        let q = Quarantine::create(&root.join("q")).unwrap();
+        q.stash(&target, true, 3).unwrap();
+        assert!(!target.exists());
+        // The application recreates its cache folder before we restore
+        fs::create_dir_all(&target).unwrap();
+
+        let report = restore(&root.join("q"), &q.info().run_id).unwrap();
+        assert_eq!(report.restored, 1);
+        assert!(report.failed.is_empty());
+        assert_eq!(fs::read(target.join("blob")).unwrap(), b"abc");
+        assert!(list_runs(&root.join("q")).unwrap().is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_restore_reports_lost_entries() {
+        let root = std::env::temp_dir().join(format!("zentify-test-quarantine-lost-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("data")).unwrap();
+        fs::write(root.join("data/a.log"), b"a").unwrap();
+        fs::write(root.join("data/b.log"), b"b").unwrap();
+        let q = Quarantine::create(&root.join("q")).unwrap();
+        q.stash(&root.join("data/a.log"), false, 1).unwrap();
+        q.stash(&root.join("data/b.log"), false, 1).unwrap();
+        // Someone cleaned up the quarantine folder by hand
+        fs::remove_file(q.dir().join("1-b.log")).unwrap();
+
+        let report = restore(&root.join("q"), &q.info().run_id).unwrap();
+        assert_eq!(report.restored, 1);
+        assert_eq!(report.missing, [root.join("data/b.log").to_string_lossy()]);
+        // The run and its record of the lost entry stay
+        assert_eq!(read_manifest(q.dir()).unwrap().len(), 1);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_stash_undone_without_manifest_line() {
+        let root = std::env::temp_dir().join(format!("zentify-test-quarantine-manifest-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let target = root.join("data/Cache");
+        fs::create_dir_all(&target).unwrap();
+        let mut q = Quarantine::create(&root.join("q")).unwrap();
+        // Every write to /dev/full fails with ENOSPC
+        q.manifest = Mutex::new(OpenOptions::new().append(true).open("/dev/full").unwrap());
+        assert!(q.stash(&target, true, 0).is_err());
+        assert!(target.is_dir());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_stash_on_another_volume() {
+        let shm = Path::new("/dev/shm");
+        let tmp = std::env::temp_dir();
+        // Needs a second writable volume
+        if !shm.is_dir() || volume_root(shm) == volume_root(&tmp) || fs::create_dir_all(shm.join(VOLUME_DIR)).is_err() { return; }
+        let root = tmp.join(format!("zentify-test-quarantine-volume-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let target = shm.join(format!("zentify-test-quarantine-{}", std::process::id()));
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("blob"), b"abc").unwrap();
+
+        let q = Quarantine::create(&root.join("q")).unwrap();
+        q.stash(&target, true, 3).unwrap();
+        assert!(!target.exists());
+        let entries = read_manifest(q.dir()).unwrap();
+        assert_eq!(entries[0].dir.as_deref().map(Path::new), Some(shm.join(VOLUME_DIR).join(&q.info().run_id).as_path()));
+
+        assert_eq!(restore(&root.join("q"), &q.info().run_id).unwrap().restored, 1);
+        assert_eq!(fs::read(target.join("blob")).unwrap(), b"abc");
+        assert!(!shm.join(VOLUME_DIR).join(&q.info().run_id).exists());
+        let _ = fs::remove_dir_all(&target);
+        let _ = fs::remove_dir(shm.join(VOLUME_DIR));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
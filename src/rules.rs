@@ -0,0 +1,476 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{chromium, gecko};
+use crate::platform::{KnownFolders, SystemFolders};
+
+/// Catalog compiled into the binary; see `rules/builtin.json` (Windows) and `rules/builtin_linux.json`.
+#[cfg(windows)]
+const BUILTIN_RULES: &str = include_str!("../rules/builtin.json");
+#[cfg(not(windows))]
+const BUILTIN_RULES: &str = include_str!("../rules/builtin_linux.json");
+
+/// Well-known base folders a rule path is resolved against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KnownFolder {
+    Temp,
+    LocalAppData,
+    AppData,
+    WinDir,
+    SystemRoot,
+    ProgramData,
+    /// User's home directory (`%USERPROFILE%` on Windows)
+    Home,
+    /// `$XDG_CACHE_HOME`, else `~/.cache`
+    XdgCache,
+    /// `$XDG_CONFIG_HOME`, else `~/.config`
+    XdgConfig,
+    /// `$XDG_DATA_HOME`, else `~/.local/share`
+    XdgData,
+    /// `/var`
+    Var,
+}
+
+impl KnownFolder {
+    pub const ALL: [KnownFolder; 11] = [
+        KnownFolder::Temp, KnownFolder::LocalAppData, KnownFolder::AppData, KnownFolder::WinDir, KnownFolder::SystemRoot,
+        KnownFolder::ProgramData, KnownFolder::Home, KnownFolder::XdgCache, KnownFolder::XdgConfig, KnownFolder::XdgData,
+        KnownFolder::Var,
+    ];
+
+    /// Location on the real machine.
+    pub fn resolve(self) -> Option<PathBuf> { SystemFolders.folder(self) }
+
+    /// Folder named by a path template variable (`LOCALAPPDATA`, `XDG_CACHE_HOME`, ...), ignoring case.
+    pub fn from_variable(name: &str) -> Option<Self> {
+        let folder = match name.to_ascii_uppercase().as_str() {
+            "TEMP" | "TMP" => KnownFolder::Temp,
+            "LOCALAPPDATA" => KnownFolder::LocalAppData,
+            "APPDATA" => KnownFolder::AppData,
+            "WINDIR" => KnownFolder::WinDir,
+            "SYSTEMROOT" => KnownFolder::SystemRoot,
+            "PROGRAMDATA" => KnownFolder::ProgramData,
+            "USERPROFILE" | "HOME" => KnownFolder::Home,
+            "XDG_CACHE_HOME" => KnownFolder::XdgCache,
+            "XDG_CONFIG_HOME" => KnownFolder::XdgConfig,
+            "XDG_DATA_HOME" => KnownFolder::XdgData,
+            _ => return None,
+        };
+        Some(folder)
+    }
+}
+
+/// Splits a path template (`%LOCALAPPDATA%/Foo/Cache`, `~/.cache/foo`) into its base folder and the path below it.
+/// `None` if it does not start with a known variable or leaves the folder through `..`.
+pub(crate) fn split_template(template: &str) -> Option<(KnownFolder, String)> {
+    let t = template.trim();
+    let (base, rest) = match t.strip_prefix('~') {
+        Some(rest) => (KnownFolder::Home, rest),
+        None => {
+            let (var, rest) = t.strip_prefix('%')?.split_once('%')?;
+            (KnownFolder::from_variable(var)?, rest)
+        }
+    };
+    if !rest.is_empty() && !rest.starts_with(['/', '\\']) { return None; }
+    let segments: Vec<&str> = rest.split(['/', '\\']).filter(|s| !s.is_empty() && *s != ".").collect();
+    if segments.contains(&"..") { return None; }
+    Some((base, segments.join("/")))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Per-user locations, always eligible
+    #[default]
+    User,
+    /// Machine-wide locations, only cleaned when system cleaning is allowed
+    System,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleKind {
+    /// Matching directories are cleaned as a whole
+    #[default]
+    Dir,
+    /// Matching files are removed individually
+    File,
+}
+
+/// How noticeable cleaning a location is; budget runs take low-risk categories first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Risk {
+    /// Scratch data nobody reads again (temp folders, dumps, error reports)
+    Low,
+    /// Caches that are rebuilt at the cost of a slower next start
+    #[default]
+    Medium,
+    /// Visible side effects: thumbnails, app state, update and prefetch data
+    High,
+}
+
+/// One cleaning location.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rule {
+    pub id: String,
+    /// Toggle group the rule belongs to (e.g. `browser_cache`)
+    pub category: String,
+    /// Toggle above the category (e.g. `dev_caches`), used when the category itself is not set
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub family: Option<String>,
+    pub base: KnownFolder,
+    /// Path relative to `base`; segments may use `*`, `?` and `{a,b}` alternatives
+    #[serde(default)] pub path: String,
+    /// Environment variable that relocates the tool's folder; when set, it replaces `base` and `path`
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub env: Option<EnvOverride>,
+    /// Treats the matched folders as browser profile stores and continues below each real profile
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub profiles: Option<ProfileSet>,
+    /// Pattern below the base whose matched folders are the rule's instances (e.g. `Packages/*`); each target
+    /// belongs to the one it lies in. Rules with `profiles` have their profiles as instances.
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub instances: Option<String>,
+    #[serde(default)] pub kind: RuleKind,
+    pub scope: Scope,
+    #[serde(default = "true_bool")] pub default_enabled: bool,
+    #[serde(default)] pub risk: Risk,
+    /// Retention used when the config has no `min_age_hours` policy for the category
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub min_age_hours: Option<u64>,
+    /// Entries inside each matched folder that are never cleaned, as a pattern relative to it (e.g. `{.X11-unix,.X*-lock}`)
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub keep: Option<String>,
+    #[serde(default)] pub description: String,
+}
+
+/// A folder named by an environment variable (e.g. `CARGO_HOME`) with the rule's path below it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EnvOverride {
+    pub var: String,
+    /// Path relative to the variable's folder, same syntax as `Rule::path`
+    #[serde(default)] pub path: String,
+}
+
+/// One of several like folders a rule cleans in (a browser profile, an app package), selectable on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Instance {
+    pub(crate) dir: PathBuf,
+    /// Name shown in the browser's profile menu, else the folder name
+    pub(crate) name: String,
+}
+
+impl Instance {
+    /// An instance named after its folder.
+    pub(crate) fn new(dir: &Path) -> Self {
+        let name = dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        Self { dir: dir.to_path_buf(), name }
+    }
+
+    /// The folder name (profile folder, package family name).
+    pub(crate) fn id(&self) -> String { self.dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default() }
+}
+
+/// A matched path with the instance it belongs to.
+pub(crate) type InstancePath = (PathBuf, Option<Instance>);
+
+/// How a browser keeps its list of profiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileLayout {
+    /// Chromium user data directory: profiles listed in `Local State`, else `Default` and `Profile N`
+    Chromium,
+    /// Firefox, Thunderbird and forks: profiles listed in `profiles.ini` and `installs.ini`, else `Profiles/*` and `*.*`
+    Gecko,
+    /// Electron app user data folder (`Local State` and `Preferences` at its top): the folder itself and its `Partitions/*`;
+    /// other folders, and those a browser rule matches or reads its profile list from, have no profiles
+    Electron,
+}
+
+/// Profiles inside the folders a rule matches, with the path to clean in each of them.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProfileSet {
+    pub layout: ProfileLayout,
+    /// Path relative to each profile, same syntax as `Rule::path`
+    #[serde(default)] pub path: String,
+    /// Base holding the profile list when it is not next to the profiles (e.g. `xdg_config` for caches in
+    /// `~/.cache`); the matched folder's path relative to the rule's base is looked up below it
+    #[serde(default)] pub state_base: Option<KnownFolder>,
+    /// Folder below `state_base` holding the profile list, when its relative path differs (e.g. `.mozilla/firefox`)
+    #[serde(default)] pub state_path: Option<String>,
+}
+
+impl ProfileSet {
+    /// Folder holding the profile list of `store`, a folder matched below `base`.
+    fn state_dir(&self, folders: &dyn KnownFolders, base: &Path, store: &Path) -> PathBuf {
+        self.state_base.and_then(|b| folders.folder(b))
+            .and_then(|sb| match &self.state_path {
+                Some(p) => Some(sb.join(p)),
+                None => store.strip_prefix(base).ok().map(|rel| sb.join(rel)),
+            })
+            .unwrap_or_else(|| store.to_path_buf())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RuleCatalog {
+    #[serde(default)] pub rules: Vec<Rule>,
+}
+
+impl RuleCatalog {
+    /// The embedded catalog only.
+    pub fn builtin() -> Self {
+        serde_json::from_str(BUILTIN_RULES).expect("embedded rule catalog is valid JSON")
+    }
+
+    /// Built-in rules extended by user rule files (`rules/*.json` next to each config location).
+    /// A user rule with the id of an existing rule replaces it.
+    pub fn load() -> Self { Self::load_from(&SystemFolders) }
+
+    /// Like [`load`](Self::load), with the rule folders taken from `folders`.
+    pub fn load_from(folders: &dyn KnownFolders) -> Self {
+        let mut catalog = Self::builtin();
+        for dir in folders.config_dirs().into_iter().map(|d| d.join("rules")) {
+            let mut files: Vec<PathBuf> = match fs::read_dir(&dir) {
+                Ok(rd) => rd.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|x| x == "json")).collect(),
+                Err(_) => continue,
+            };
+            files.sort();
+            for f in files {
+                match fs::read_to_string(&f).map_err(|e| e.to_string())
+                    .and_then(|s| serde_json::from_str::<RuleCatalog>(&s).map_err(|e| e.to_string()))
+                {
+                    Ok(extra) => catalog.extend(extra),
+                    Err(e) => log::warn!("ignoring rule file {}: {}", f.display(), e),
+                }
+            }
+        }
+        catalog
+    }
+
+    pub fn extend(&mut self, other: RuleCatalog) {
+        for rule in other.rules {
+            match self.rules.iter_mut().find(|r| r.id == rule.id) {
+                Some(existing) => *existing = rule,
+                None => self.rules.push(rule),
+            }
+        }
+    }
+
+    /// Risk of a category: that of its riskiest rule.
+    pub fn category_risk(&self, category: &str) -> Risk {
+        self.rules.iter().filter(|r| r.category == category).map(|r| r.risk).max().unwrap_or_default()
+    }
+
+    /// Distinct categories in catalog order.
+    pub fn categories(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = Vec::new();
+        for r in &self.rules {
+            if !out.contains(&r.category.as_str()) { out.push(&r.category); }
+        }
+        out
+    }
+}
+
+impl Rule {
+    /// Existing paths matched by this rule (directories or files depending on `kind`).
+    pub fn expand(&self) -> Vec<PathBuf> { self.expand_in(&SystemFolders) }
+
+    /// Like [`expand`](Self::expand), with the base folder taken from `folders`.
+    pub fn expand_in(&self, folders: &dyn KnownFolders) -> Vec<PathBuf> {
+        self.expand_instances_in(folders).into_iter().map(|(p, _)| p).collect()
+    }
+
+    /// Like [`expand_in`](Self::expand_in), with the instance each path belongs to.
+    pub(crate) fn expand_instances_in(&self, folders: &dyn KnownFolders) -> Vec<InstancePath> { self.expand_stores_in(folders).0 }
+
+    /// Like [`expand_instances_in`](Self::expand_instances_in), plus the folders profiles were found in
+    /// (browser user data, Electron app folders); always empty for rules without `profiles`.
+    pub(crate) fn expand_stores_in(&self, folders: &dyn KnownFolders) -> (Vec<InstancePath>, Vec<PathBuf>) {
+        let Some((base, path)) = self.resolve(folders) else { return (Vec::new(), Vec::new()) };
+        let mut out: Vec<InstancePath> = Vec::new();
+        let mut stores: Vec<PathBuf> = Vec::new();
+        match &self.profiles {
+            None => {
+                let instances: Vec<PathBuf> = self.instances.as_deref().map(|i| expand_pattern(&base, i)).unwrap_or_default();
+                out.extend(expand_pattern(&base, path).into_iter().map(|p| {
+                    let instance = instances.iter().find(|d| p.starts_with(d)).map(|d| Instance::new(d));
+                    (p, instance)
+                }));
+            }
+            Some(set) => {
+                for store in expand_pattern(&base, path).into_iter().filter(|p| p.is_dir()) {
+                    let state_dir = set.state_dir(folders, &base, &store);
+                    let found = match set.layout {
+                        ProfileLayout::Chromium => chromium::profiles(&store, &state_dir),
+                        ProfileLayout::Gecko => gecko::profiles(&store, &state_dir),
+                        ProfileLayout::Electron => chromium::app_profiles(&store),
+                    };
+                    if !found.is_empty() { stores.push(store.clone()); }
+                    for profile in found {
+                        out.extend(expand_pattern(&profile.dir, &set.path).into_iter().map(|p| (p, Some(profile.clone()))));
+                    }
+                }
+            }
+        }
+        out.retain(|(p, _)| match self.kind { RuleKind::Dir => p.is_dir(), RuleKind::File => p.is_file() });
+        (out, stores)
+    }
+
+    /// Folders a browser rule (any profile layout but Electron) matches, and where it looks up their profile lists,
+    /// whether profiles were found in them or not. Electron rules must not take these for apps.
+    pub(crate) fn browser_roots_in(&self, folders: &dyn KnownFolders) -> Vec<PathBuf> {
+        let Some(set) = self.profiles.as_ref().filter(|s| s.layout != ProfileLayout::Electron) else { return Vec::new() };
+        let Some((base, path)) = self.resolve(folders) else { return Vec::new() };
+        expand_pattern(&base, path).into_iter()
+            .flat_map(|store| [set.state_dir(folders, &base, &store), store])
+            .filter(|p| p.is_dir())
+            .collect()
+    }
+
+    /// Base folder and path below it, following `env` when the variable is set.
+    fn resolve(&self, folders: &dyn KnownFolders) -> Option<(PathBuf, &str)> {
+        match self.env.as_ref().and_then(|e| folders.env_dir(&e.var).map(|d| (d, e.path.as_str()))) {
+            Some(r) => Some(r),
+            None => folders.folder(self.base).map(|b| (b, self.path.as_str())),
+        }
+    }
+
+    /// Segments of `path` holding wildcards: the fewer, the more specific the rule.
+    pub(crate) fn wildcard_segments(&self) -> usize {
+        expand_braces(&self.path).iter().map(|p| p.split(['/', '\\']).filter(|s| has_wildcards(s)).count()).max().unwrap_or(0)
+    }
+}
+
+/// Paths below `base` matching `path` (wildcards and alternatives), existing or not at the leaf.
+pub(crate) fn expand_pattern(base: &Path, path: &str) -> Vec<PathBuf> {
+    let mut out: Vec<PathBuf> = Vec::new();
+    for pattern in expand_braces(path) {
+        let segments: Vec<&str> = pattern.split(['/', '\\']).filter(|s| !s.is_empty()).collect();
+        collect_matches(base, &segments, &mut out);
+    }
+    out
+}
+
+fn collect_matches(dir: &Path, segments: &[&str], out: &mut Vec<PathBuf>) {
+    let Some((first, rest)) = segments.split_first() else {
+        out.push(dir.to_path_buf());
+        return;
+    };
+    if !has_wildcards(first) {
+        collect_matches(&dir.join(first), rest, out);
+        return;
+    }
+    if let Ok(rd) = fs::read_dir(dir) {
+        for e in rd.flatten() {
+            let name = e.file_name();
+            if wildcard_match(first, &name.to_string_lossy()) {
+                collect_matches(&e.path(), rest, out);
+            }
+        }
+    }
+}
+
+fn has_wildcards(s: &str) -> bool { s.contains(['*', '?']) }
+
+/// Expands `{a,b}` alternatives (nesting allowed) into plain patterns. Braces without a comma at their level are
+/// literal text, as in GUID folder names (`{3F2504E0-4F89-11D3-9A0C-0305E82C3301}`).
+pub(crate) fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else { return vec![pattern.to_string()] };
+    let mut depth = 0;
+    let mut close = None;
+    let mut splits = Vec::new();
+    for (i, c) in pattern[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => { depth -= 1; if depth == 0 { close = Some(open + i); break; } }
+            ',' if depth == 1 => splits.push(open + i),
+            _ => {}
+        }
+    }
+    let Some(close) = close else { return vec![pattern.to_string()] };
+    let (head, tail) = (&pattern[..open], &pattern[close + 1..]);
+    if splits.is_empty() {
+        let mut out = Vec::new();
+        for inner in expand_braces(&pattern[open + 1..close]) {
+            out.extend(expand_braces(tail).into_iter().map(|t| format!("{}{{{}}}{}", head, inner, t)));
+        }
+        return out;
+    }
+    let mut bounds = vec![open];
+    bounds.extend(splits);
+    bounds.push(close);
+    let mut out = Vec::new();
+    for w in bounds.windows(2) {
+        let alt = &pattern[w[0] + 1..w[1]];
+        out.extend(expand_braces(&format!("{}{}{}", head, alt, tail)));
+    }
+    out
+}
+
+/// Matches a single path segment against `*`/`?` wildcards (ASCII case-insensitive on Windows).
+pub(crate) fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let fold = |s: &str| -> Vec<char> {
+        if cfg!(windows) { s.to_ascii_lowercase().chars().collect() } else { s.chars().collect() }
+    };
+    let (p, n) = (fold(pattern), fold(name));
+    // Iterative matcher with single-star backtracking
+    let (mut pi, mut ni) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ni));
+            pi += 1;
+        } else if let Some((sp, sn)) = star {
+            pi = sp + 1;
+            ni = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+fn true_bool() -> bool { true }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_catalogs_parse() {
+        let windows: RuleCatalog = serde_json::from_str(include_str!("../rules/builtin.json")).unwrap();
+        assert!(windows.categories().contains(&"browser_cache"));
+        let prefetch = windows.rules.iter().find(|r| r.category == "prefetch").unwrap();
+        assert!(!prefetch.default_enabled);
+        // Every Chromium product comes with its product-level caches, and channels expand from one entry
+        for r in windows.rules.iter().filter(|r| r.category == "browser_cache" && r.profiles.as_ref().is_some_and(|p| p.layout == ProfileLayout::Chromium)) {
+            assert!(windows.rules.iter().any(|o| o.id == r.id.replace("_cache", "_product_cache")), "{}", r.id);
+        }
+        let crash = windows.rules.iter().find(|r| r.id == "chromium_crash_reports").unwrap();
+        assert!(expand_braces(&crash.path).contains(&"Google/Chrome SxS/User Data/Crashpad/reports".to_string()));
+
+        let linux: RuleCatalog = serde_json::from_str(include_str!("../rules/builtin_linux.json")).unwrap();
+        assert!(linux.categories().contains(&"browser_cache"));
+        let tmp = linux.rules.iter().find(|r| r.id == "tmp_dir").unwrap();
+        assert!(tmp.min_age_hours.is_some());
+        assert!(linux.rules.iter().filter(|r| r.category == "journal").all(|r| r.scope == Scope::System));
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert_eq!(expand_braces("a/{b,c/d}/{e,f}"), vec!["a/b/e", "a/b/f", "a/c/d/e", "a/c/d/f"]);
+        assert_eq!(expand_braces("Apps/{3F2504E0-4F89-11D3-9A0C-0305E82C3301}/{Cache,Logs}"),
+            vec!["Apps/{3F2504E0-4F89-11D3-9A0C-0305E82C3301}/Cache", "Apps/{3F2504E0-4F89-11D3-9A0C-0305E82C3301}/Logs"]);
+        assert!(wildcard_match("thumbcache*", "thumbcache_32.db"));
+        assert!(wildcard_match("Profile ?", "Profile 3"));
+        assert!(!wildcard_match("*.db", "thumbcache.dbx"));
+        assert_eq!(split_template("%LocalAppData%/Foo\\Cache"), Some((KnownFolder::LocalAppData, "Foo/Cache".to_string())));
+        assert_eq!(split_template("~/.cache/tool/"), Some((KnownFolder::Home, ".cache/tool".to_string())));
+        assert_eq!(split_template("%TEMP%/../Secrets"), None);
+        assert_eq!(split_template("%NOPE%/x"), None);
+        assert_eq!(split_template("C:/Tools/cache"), None);
+    }
+}
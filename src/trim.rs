@@ -0,0 +1,139 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::links::{FileId, LinkTracker};
+use crate::walker::{self, Job, TreeWalk, Worker};
+use crate::{allocated_size, set_writable, target_finished, EntryFilter, EntryKind, RunCtx, Target, TreeCount};
+
+/// A file seen by a trim walk.
+pub(crate) struct CachedFile {
+    path: PathBuf,
+    bytes: u64,
+    allocated: u64,
+    /// Later of atime and mtime
+    last_used: SystemTime,
+    /// The category's retention filter lets it go
+    evictable: bool,
+    /// Identity and link count when the file has several hard links
+    link: Option<(FileId, u64)>,
+}
+
+impl CachedFile {
+    pub(crate) fn new(path: PathBuf, md: &fs::Metadata, evictable: bool, link: Option<(FileId, u64)>) -> Self {
+        let last_used = [md.modified().ok(), md.accessed().ok()].into_iter().flatten().max().unwrap_or(SystemTime::UNIX_EPOCH);
+        let allocated = allocated_size(&path, md);
+        Self { path, bytes: md.len(), allocated, last_used, evictable, link }
+    }
+
+    /// Adds the file to `count`; hard-linked data goes to `links` instead, to be settled once.
+    fn book(&self, count: &mut TreeCount, links: &LinkTracker) {
+        match self.link {
+            Some((id, nlink)) => links.record(id, nlink, self.bytes, self.allocated),
+            None => {
+                count.bytes += self.bytes;
+                count.allocated += self.allocated;
+            }
+        }
+        count.files += 1;
+    }
+}
+
+/// Files to evict so that the rest fits in `keep` bytes on disk: the most recently used stay,
+/// everything older than the first file that no longer fits goes.
+fn evictions(mut files: Vec<CachedFile>, keep: u64) -> Vec<CachedFile> {
+    files.sort_by_key(|f| Reverse(f.last_used));
+    let mut kept = 0u64;
+    let mut full = false;
+    files.retain(|f| {
+        if !f.evictable || (!full && kept + f.allocated <= keep) {
+            kept += f.allocated;
+            return false;
+        }
+        full = true;
+        true
+    });
+    files
+}
+
+fn total(files: &[CachedFile]) -> TreeCount {
+    let links = LinkTracker::default();
+    let mut count = TreeCount::default();
+    for f in files { f.book(&mut count, &links); }
+    let t = links.totals();
+    count.bytes += t.freed_bytes;
+    count.allocated += t.freed_allocated;
+    count.unfreed = t.unfreed_bytes;
+    count
+}
+
+/// Trims a directory target down to `keep` bytes, oldest files first. Directories are left in place.
+pub(crate) fn trim_target<'a>(target: &'a Target, ctx: &'a RunCtx<'a>, filter: EntryFilter, keep: u64, w: &Worker<'_, Job<'a>>) {
+    let dir = target.path.as_path();
+    let seen: Arc<Mutex<Vec<CachedFile>>> = Arc::default();
+    let list = seen.clone();
+    TreeWalk::new(filter, Some(ctx), false).collect_into(seen).start(dir, w, move |_, _, _| {
+        let files = list.lock().map(|mut v| std::mem::take(&mut *v)).unwrap_or_default();
+        let mut count = TreeCount::default();
+        for f in evictions(files, keep) {
+            if ctx.cancelled() { break; }
+            if !ctx.cfg.dry_run {
+                set_writable(&f.path);
+                if let Err(e) = ctx.dispose(&f.path, false, f.bytes) {
+                    ctx.failed(&f.path, &e);
+                    continue;
+                }
+            }
+            ctx.removed(&f.path, EntryKind::File, 1, 0, f.bytes);
+            // Only evicted files reach the run's link tracker
+            f.book(&mut count, &ctx.stats.hardlinks);
+        }
+        ctx.stats.add_count(&count);
+        if count.files > 0 { ctx.stats.add_cleaned_dir(dir); }
+        target_finished(ctx, target);
+    });
+}
+
+/// What trimming `root` down to `keep` bytes would remove.
+pub(crate) fn trim_stats(root: &Path, filter: &EntryFilter, keep: u64, threads: usize) -> TreeCount {
+    let seen: Arc<Mutex<Vec<CachedFile>>> = Arc::default();
+    walker::walk_tree(root, TreeWalk::new(filter.clone(), None, false).collect_into(seen.clone()), threads);
+    let files = seen.lock().map(|mut v| std::mem::take(&mut *v)).unwrap_or_default();
+    total(&evictions(files, keep))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn file(name: &str, age_secs: u64, size: u64) -> CachedFile {
+        let last_used = SystemTime::now() - Duration::from_secs(age_secs);
+        CachedFile { path: PathBuf::from(name), bytes: size, allocated: size, last_used, evictable: true, link: None }
+    }
+
+    #[test]
+    fn test_evictions_keep_newest_up_to_cap() {
+        let files = vec![file("old", 300, 40), file("new", 10, 50), file("mid", 100, 30), file("tiny_old", 400, 1)];
+        let out: Vec<String> = evictions(files, 85).iter().map(|f| f.path.to_string_lossy().to_string()).collect();
+        // "new" and "mid" fit (80); "old" does not, and nothing older stays even if it would fit
+        assert_eq!(out, ["old", "tiny_old"]);
+        assert_eq!(total(&evictions(vec![file("a", 1, 10)], 0)).bytes, 10);
+    }
+
+    #[test]
+    fn test_trim_stats_walks_target() {
+        let root = std::env::temp_dir().join(format!("zentify-test-trim-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a.bin"), b"1234").unwrap();
+        fs::write(root.join("sub/b.bin"), b"12").unwrap();
+        let filter = EntryFilter::default();
+        let all = trim_stats(&root, &filter, 0, 2);
+        assert_eq!((all.files, all.bytes, all.dirs), (2, 6, 0));
+        assert_eq!(trim_stats(&root, &filter, u64::MAX / 2, 2).files, 0);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
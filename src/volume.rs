@@ -0,0 +1,138 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// Change of free space on one volume a run touched, as reported in `Summary::volumes`.
+/// It covers everything that happened on the volume meanwhile, including other processes writing to it.
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeDelta {
+    /// Mount point (Unix) or volume root such as `C:\` (Windows)
+    pub volume: String,
+    pub free_before: u64,
+    pub free_after: u64,
+    /// `free_after - free_before`; negative if the volume filled up during the run
+    pub freed: i64,
+}
+
+impl fmt::Display for VolumeDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.freed < 0 { "-" } else { "+" };
+        write!(f, "{}: {}{} free space (now {})", self.volume, sign, crate::format_bytes(self.freed.unsigned_abs()), crate::format_bytes(self.free_after))
+    }
+}
+
+/// Free space of every volume below a set of targets, sampled when the run starts.
+pub(crate) struct VolumeSampler {
+    volumes: Vec<(PathBuf, u64)>,
+}
+
+impl VolumeSampler {
+    pub(crate) fn sample<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Self {
+        let mut volumes: Vec<(PathBuf, u64)> = Vec::new();
+        for p in paths {
+            let Some(root) = volume_root(p) else { continue };
+            if volumes.iter().any(|(v, _)| *v == root) { continue; }
+            match free_space(&root) {
+                Ok(free) => volumes.push((root, free)),
+                Err(e) => log::warn!("cannot read free space of {}: {}", root.display(), e),
+            }
+        }
+        Self { volumes }
+    }
+
+    /// Samples again and returns the per-volume change.
+    pub(crate) fn finish(self) -> Vec<VolumeDelta> {
+        self.volumes
+            .into_iter()
+            .filter_map(|(root, before)| {
+                let after = free_space(&root).ok()?;
+                Some(VolumeDelta {
+                    volume: root.to_string_lossy().to_string(),
+                    free_before: before,
+                    free_after: after,
+                    freed: after as i64 - before as i64,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The mount point holding `p` (or its nearest existing ancestor): the topmost ancestor on the same device.
+#[cfg(unix)]
+pub(crate) fn volume_root(p: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+    let p = p.ancestors().find_map(|a| a.canonicalize().ok())?;
+    let dev = std::fs::metadata(&p).ok()?.dev();
+    let mut root = p.clone();
+    for a in p.ancestors().skip(1) {
+        match std::fs::metadata(a) {
+            Ok(md) if md.dev() == dev => root = a.to_path_buf(),
+            _ => break,
+        }
+    }
+    Some(root)
+}
+
+#[cfg(windows)]
+pub(crate) fn volume_root(p: &Path) -> Option<PathBuf> {
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use windows_sys::Win32::Storage::FileSystem::GetVolumePathNameW;
+    let p = p.ancestors().find(|a| a.exists())?;
+    let wide: Vec<u16> = p.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut buf = [0u16; 1024];
+    let ok = unsafe { GetVolumePathNameW(wide.as_ptr(), buf.as_mut_ptr(), buf.len() as u32) };
+    if ok == 0 { return None; }
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    Some(PathBuf::from(std::ffi::OsString::from_wide(&buf[..len])))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn volume_root(_p: &Path) -> Option<PathBuf> { None }
+
+/// Free bytes on the volume, including blocks reserved for root (they are freed just the same).
+#[cfg(unix)]
+fn free_space(root: &Path) -> io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let c = std::ffi::CString::new(root.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c.as_ptr(), &mut st) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)] // the field types differ between platforms
+    Ok(st.f_bfree as u64 * st.f_frsize as u64)
+}
+
+#[cfg(windows)]
+fn free_space(root: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+    let wide: Vec<u16> = root.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let (mut avail, mut total, mut free) = (0u64, 0u64, 0u64);
+    // The total free count, not the caller's quota-limited share
+    let ok = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut avail, &mut total, &mut free) };
+    if ok == 0 { return Err(io::Error::last_os_error()); }
+    Ok(free)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn free_space(_root: &Path) -> io::Result<u64> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "free space not available on this platform"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampler_groups_paths_by_volume() {
+        let tmp = std::env::temp_dir();
+        let root = volume_root(&tmp.join("does-not-exist/yet")).unwrap();
+        assert!(tmp.canonicalize().unwrap().starts_with(&root));
+        let sampler = VolumeSampler::sample([tmp.as_path(), tmp.join("a").as_path()]);
+        let deltas = sampler.finish();
+        assert_eq!(deltas.len(), 1);
+        assert!(deltas[0].free_before > 0);
+    }
+}
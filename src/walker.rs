@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::keep;
+use crate::quarantine;
+use crate::links::{hardlink_id, LinkTracker};
+use crate::trim::CachedFile;
+use crate::{
+    allocated_size, is_reparse_metadata, is_special_file, owned_by, schedule_delete_on_reboot, set_writable, sticky_owner, CleanEvent, EntryFilter,
+    EntryKind, RunCtx, TreeCount,
+};
+
+/// Unit of work for the pool: a whole cleaning target (index into the run's list) or one directory of a walk.
+pub(crate) enum Job<'a> {
+    Target(usize),
+    Dir(Arc<DirNode<'a>>),
+}
+
+/// Work-stealing pool: every worker pops its own deque from the back (depth first, good locality)
+/// and steals from the front of the others (the shallowest, usually largest pending subtrees).
+pub(crate) struct Worker<'p, J> {
+    pool: &'p Pool<J>,
+    index: usize,
+}
+
+struct Pool<J> {
+    deques: Vec<Mutex<VecDeque<J>>>,
+    /// Jobs queued or running; the pool is done when this drops to zero
+    outstanding: AtomicUsize,
+    idle: Mutex<()>,
+    wake: Condvar,
+}
+
+impl<J> Worker<'_, J> {
+    pub(crate) fn push(&self, job: J) {
+        self.pool.outstanding.fetch_add(1, Ordering::AcqRel);
+        if let Ok(mut d) = self.pool.deques[self.index].lock() { d.push_back(job); }
+        self.pool.wake.notify_one();
+    }
+}
+
+impl<J> Pool<J> {
+    fn pop(&self, index: usize) -> Option<J> {
+        if let Some(job) = self.deques[index].lock().ok().and_then(|mut d| d.pop_back()) { return Some(job); }
+        let n = self.deques.len();
+        (1..n).find_map(|k| self.deques[(index + k) % n].lock().ok().and_then(|mut d| d.pop_front()))
+    }
+}
+
+/// Runs `jobs` (and everything they push) on `threads` workers until no work is left.
+pub(crate) fn run<J: Send>(threads: usize, jobs: Vec<J>, handle: impl Fn(J, &Worker<J>) + Sync) {
+    let threads = threads.max(1);
+    let pool = Pool {
+        deques: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+        outstanding: AtomicUsize::new(jobs.len()),
+        idle: Mutex::new(()),
+        wake: Condvar::new(),
+    };
+    for (i, job) in jobs.into_iter().enumerate() {
+        if let Ok(mut d) = pool.deques[i % threads].lock() { d.push_front(job); }
+    }
+    thread::scope(|s| {
+        for index in 0..threads {
+            let (pool, handle) = (&pool, &handle);
+            s.spawn(move || {
+                let me = Worker { pool, index };
+                loop {
+                    if let Some(job) = pool.pop(index) {
+                        handle(job, &me);
+                        if pool.outstanding.fetch_sub(1, Ordering::AcqRel) == 1 { pool.wake.notify_all(); }
+                        continue;
+                    }
+                    if pool.outstanding.load(Ordering::Acquire) == 0 { break; }
+                    // Someone is still working and may push more; the timeout covers a missed wake-up
+                    if let Ok(guard) = pool.idle.lock() {
+                        let _ = pool.wake.wait_timeout(guard, Duration::from_millis(5));
+                    }
+                }
+            });
+        }
+    });
+}
+
+type OnDone<'a> = Box<dyn FnOnce(TreeCount, bool, &Worker<'_, Job<'a>>) + Send + 'a>;
+
+/// One parallel walk below a root directory, counting (and with `delete` removing) what the filter allows.
+pub(crate) struct TreeWalk<'a> {
+    filter: EntryFilter,
+    /// Run context for events, failures and cancellation; required for deleting
+    ctx: Option<&'a RunCtx<'a>>,
+    delete: bool,
+    /// Locked files directly below the root are scheduled for deletion on reboot (Windows)
+    reboot_fallback: bool,
+    /// Only looks for entries that must stay, without counting; the first one found ends the walk
+    probe: bool,
+    /// Set by a probe once it found an entry that must stay
+    found: AtomicBool,
+    bytes: AtomicU64,
+    allocated: AtomicU64,
+    files: AtomicU64,
+    dirs: AtomicU64,
+    links: AtomicU64,
+    /// Multiply-linked files of a walk that only counts; what a run removes is tracked in its stats
+    hardlinks: LinkTracker,
+    /// Trim walks record every file here (delete must be off)
+    collect: Option<Arc<Mutex<Vec<CachedFile>>>>,
+    /// Called once the whole tree is processed, with the totals and whether the root is now empty
+    on_done: Mutex<Option<OnDone<'a>>>,
+}
+
+/// A directory of a walk; it completes once its own listing and all its subdirectories are done.
+pub(crate) struct DirNode<'a> {
+    walk: Arc<TreeWalk<'a>>,
+    path: PathBuf,
+    parent: Option<Arc<DirNode<'a>>>,
+    /// The listing itself plus every subdirectory still in flight
+    pending: AtomicUsize,
+    emptied: AtomicBool,
+    /// Filter verdict on the directory itself, taken before its contents change its mtime
+    allowed: bool,
+}
+
+impl<'a> TreeWalk<'a> {
+    pub(crate) fn new(filter: EntryFilter, ctx: Option<&'a RunCtx<'a>>, delete: bool) -> Self {
+        Self {
+            filter,
+            ctx,
+            delete: delete && ctx.is_some(),
+            reboot_fallback: false,
+            probe: false,
+            found: AtomicBool::new(false),
+            bytes: AtomicU64::new(0),
+            allocated: AtomicU64::new(0),
+            files: AtomicU64::new(0),
+            dirs: AtomicU64::new(0),
+            links: AtomicU64::new(0),
+            hardlinks: LinkTracker::default(),
+            collect: None,
+            on_done: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn collect_into(mut self, list: Arc<Mutex<Vec<CachedFile>>>) -> Self {
+        self.collect = Some(list);
+        self
+    }
+
+    pub(crate) fn with_reboot_fallback(mut self) -> Self {
+        self.reboot_fallback = true;
+        self
+    }
+
+    /// Turns the walk into a check whether the whole tree may go at once: it stops at the first marker, excluded entry,
+    /// special file or unremovable entry, and the root then reports as not emptied. Nothing is counted or removed.
+    pub(crate) fn probe(mut self) -> Self {
+        self.probe = true;
+        self
+    }
+
+    /// Queues the walk below `root` (which itself is neither counted nor removed).
+    pub(crate) fn start(self, root: &Path, w: &Worker<'_, Job<'a>>, on_done: impl FnOnce(TreeCount, bool, &Worker<'_, Job<'a>>) + Send + 'a) {
+        w.push(self.root_job(root, on_done));
+    }
+
+    fn root_job(self, root: &Path, on_done: impl FnOnce(TreeCount, bool, &Worker<'_, Job<'a>>) + Send + 'a) -> Job<'a> {
+        if let Ok(mut slot) = self.on_done.lock() { *slot = Some(Box::new(on_done)); }
+        Job::Dir(Arc::new(DirNode {
+            walk: Arc::new(self),
+            path: root.to_path_buf(),
+            parent: None,
+            pending: AtomicUsize::new(1),
+            emptied: AtomicBool::new(true),
+            allowed: true,
+        }))
+    }
+
+    fn add(&self, c: TreeCount) {
+        self.bytes.fetch_add(c.bytes, Ordering::Relaxed);
+        self.allocated.fetch_add(c.allocated, Ordering::Relaxed);
+        self.files.fetch_add(c.files, Ordering::Relaxed);
+        self.dirs.fetch_add(c.dirs, Ordering::Relaxed);
+        self.links.fetch_add(c.links, Ordering::Relaxed);
+    }
+
+    /// Totals so far. A walk that only counts also settles its hard-linked files here.
+    fn total(&self) -> TreeCount {
+        let mut count = TreeCount {
+            bytes: self.bytes.load(Ordering::Relaxed),
+            allocated: self.allocated.load(Ordering::Relaxed),
+            files: self.files.load(Ordering::Relaxed),
+            dirs: self.dirs.load(Ordering::Relaxed),
+            links: self.links.load(Ordering::Relaxed),
+            unfreed: 0,
+        };
+        if !self.books_run_links() {
+            let t = self.hardlinks.totals();
+            count.bytes += t.freed_bytes;
+            count.allocated += t.freed_allocated;
+            count.unfreed = t.unfreed_bytes;
+        }
+        count
+    }
+
+    /// Whether the walk's files count as removed by the run: it deletes them, or it is the dry-run stand-in for that.
+    /// Counting passes ahead of a quarantine move and trim listings keep their links to themselves.
+    fn books_run_links(&self) -> bool {
+        self.collect.is_none() && self.ctx.is_some_and(|c| self.delete || c.cfg.dry_run)
+    }
+
+    fn link_tracker(&self) -> &LinkTracker {
+        match self.ctx {
+            Some(ctx) if self.books_run_links() => &ctx.stats.hardlinks,
+            _ => &self.hardlinks,
+        }
+    }
+
+    fn cancelled(&self) -> bool { self.ctx.is_some_and(|c| c.cancelled()) }
+
+    /// Whether a probe already has its answer.
+    fn settled(&self) -> bool { self.probe && self.found.load(Ordering::Relaxed) }
+}
+
+impl DirNode<'_> {
+    /// Something in this directory stays, so it cannot be removed.
+    fn keeps(&self) {
+        self.emptied.store(false, Ordering::Relaxed);
+        if self.walk.probe { self.walk.found.store(true, Ordering::Relaxed); }
+    }
+}
+
+/// Lists one directory: files and links are handled inline, subdirectories become new jobs.
+pub(crate) fn process_dir<'a>(node: Arc<DirNode<'a>>, w: &Worker<'_, Job<'a>>) {
+    let walk = &node.walk;
+    let top_level = node.parent.is_none();
+    // A folder with the keep marker stays as it is
+    if walk.settled() || keep::has_marker(&node.path) {
+        node.keeps();
+        return complete(node, w);
+    }
+    match fs::read_dir(&node.path) {
+        Err(_) => node.keeps(),
+        Ok(rd) => {
+            let owner = sticky_owner(&node.path);
+            for e in rd.flatten() {
+                // A cancelled run leaves the rest of the tree alone
+                if walk.cancelled() || walk.settled() { node.keeps(); break; }
+                // The entry's own metadata (never following links) comes with the listing on Windows
+                let Ok(md) = e.metadata() else { node.keeps(); continue };
+                let p = e.path();
+                // Quarantined entries of this or earlier runs on this volume
+                if e.file_name() == quarantine::VOLUME_DIR { node.keeps(); continue; }
+                if walk.filter.exclude.matches(&p) { node.keeps(); continue; }
+                // Other users' entries in a shared sticky folder cannot be removed; they are not ours to report either
+                if owner.is_some_and(|uid| !owned_by(&md, uid)) { node.keeps(); continue; }
+                if md.is_dir() && !is_reparse_metadata(&md) && !md.file_type().is_symlink() {
+                    node.pending.fetch_add(1, Ordering::AcqRel);
+                    let child = DirNode {
+                        walk: walk.clone(),
+                        allowed: walk.filter.allows_dir(&md),
+                        path: p,
+                        parent: Some(node.clone()),
+                        pending: AtomicUsize::new(1),
+                        emptied: AtomicBool::new(true),
+                    };
+                    w.push(Job::Dir(Arc::new(child)));
+                    continue;
+                }
+                if is_special_file(&md) { node.keeps(); continue; }
+                if walk.probe { continue; }
+                if !remove_leaf(walk, &p, &md, top_level) { node.keeps(); }
+            }
+        }
+    }
+    complete(node, w);
+}
+
+/// Counts (and when deleting, removes) a file or link. Returns whether it is gone (or would be).
+fn remove_leaf(walk: &TreeWalk, p: &Path, md: &fs::Metadata, top_level: bool) -> bool {
+    let is_link = is_reparse_metadata(md) || md.file_type().is_symlink();
+    let allowed = walk.filter.allows(md);
+    // Data behind several hard links is booked once, when the walk or run settles its links
+    let shared = if is_link || !(allowed || walk.collect.is_some()) { None } else { hardlink_id(p, md) };
+    if let Some(list) = walk.collect.as_ref().filter(|_| !is_link) {
+        let f = CachedFile::new(p.to_path_buf(), md, allowed, shared);
+        if let Ok(mut v) = list.lock() { v.push(f); }
+    }
+    // Never follow links; remove the link itself
+    if !allowed { return false; }
+    let count = if is_link {
+        TreeCount { links: 1, ..TreeCount::default() }
+    } else {
+        TreeCount { bytes: md.len(), allocated: allocated_size(p, md), files: 1, ..TreeCount::default() }
+    };
+    let book = |walk: &TreeWalk| match shared {
+        Some((id, nlink)) => {
+            walk.link_tracker().record(id, nlink, count.bytes, count.allocated);
+            walk.add(TreeCount { files: 1, ..TreeCount::default() });
+        }
+        None => walk.add(count),
+    };
+    let Some(ctx) = walk.ctx.filter(|_| walk.delete) else {
+        book(walk);
+        return true;
+    };
+    let res = if is_link {
+        ctx.dispose_link(p)
+    } else {
+        // Avoid an extra stat per file: the listing already told us whether it is read-only
+        if md.permissions().readonly() { set_writable(p); }
+        ctx.dispose(p, false, md.len())
+    };
+    match res {
+        Ok(()) => {
+            let kind = if is_link { EntryKind::Link } else { EntryKind::File };
+            ctx.removed(p, kind, count.files, 0, count.bytes);
+            book(walk);
+            true
+        }
+        Err(e) => {
+            // On Windows, schedule deletion on next reboot for locked files (never in quarantine mode)
+            if top_level && walk.reboot_fallback && !is_link && ctx.quarantine.is_none() && schedule_delete_on_reboot(p) {
+                ctx.emit(CleanEvent::EntryScheduled { path: p, bytes: count.bytes });
+                book(walk);
+            } else {
+                ctx.failed(p, &e);
+            }
+            false
+        }
+    }
+}
+
+/// Drops one unit of pending work from `node`; the last one removes the emptied directory
+/// and propagates up to its parent, or finishes the walk at the root.
+fn complete<'a>(node: Arc<DirNode<'a>>, w: &Worker<'_, Job<'a>>) {
+    let mut node = node;
+    loop {
+        if node.pending.fetch_sub(1, Ordering::AcqRel) != 1 { return; }
+        let walk = &node.walk;
+        let emptied = node.emptied.load(Ordering::Relaxed);
+        let Some(parent) = node.parent.clone() else {
+            let total = walk.total();
+            let on_done = walk.on_done.lock().ok().and_then(|mut s| s.take());
+            if let Some(f) = on_done { f(total, emptied, w); }
+            return;
+        };
+        let mut gone = emptied && node.allowed;
+        if gone {
+            if let Some(ctx) = walk.ctx.filter(|_| walk.delete) {
+                // Emptied directories are simply removed, even in quarantine mode; restore recreates parents
+                set_writable(&node.path);
+                match fs::remove_dir(&node.path) {
+                    Ok(()) => ctx.removed(&node.path, EntryKind::Dir, 0, 1, 0),
+                    Err(e) => { ctx.failed(&node.path, &e); gone = false; }
+                }
+            }
+        }
+        if gone {
+            walk.add(TreeCount { dirs: 1, ..TreeCount::default() });
+        } else {
+            parent.emptied.store(false, Ordering::Relaxed);
+        }
+        node = parent;
+    }
+}
+
+/// Walks `root` on a pool of its own and returns the totals and whether `root` ended up empty.
+pub(crate) fn walk_tree<'a>(root: &Path, walk: TreeWalk<'a>, threads: usize) -> (TreeCount, bool) {
+    let result: Arc<Mutex<Option<(TreeCount, bool)>>> = Arc::default();
+    let slot = result.clone();
+    let job = walk.root_job(root, move |count, emptied, _| {
+        if let Ok(mut r) = slot.lock() { *r = Some((count, emptied)); }
+    });
+    run(threads, vec![job], |job, w| {
+        if let Job::Dir(node) = job { process_dir(node, w); }
+    });
+    let out = result.lock().ok().and_then(|mut r| r.take());
+    out.unwrap_or_default()
+}